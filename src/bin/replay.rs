@@ -0,0 +1,88 @@
+// Exports a recorded run as an animated SVG or as text frames, for sharing it without the game.
+//
+//   replay [FILE] [--ascii] [--stride N] [--from MOVE] [--to MOVE] [--fps N] [--cell PX]
+//          [--theme NAME] [--out PATH]
+//
+// FILE defaults to the last run the game recorded, the theme to the one picked in the settings.
+// The output goes to stdout unless --out is given.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use game::snake::{ExportOptions, Palette, Replay, Settings, Themes};
+
+struct Args {
+    file: PathBuf,
+    ascii: bool,
+    theme: Option<String>,
+    out: Option<PathBuf>,
+    options: ExportOptions,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self {
+            file: Replay::last_path(),
+            ascii: false,
+            theme: None,
+            out: None,
+            options: ExportOptions::default(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            let number = |value: String| {
+                value
+                    .parse::<usize>()
+                    .map_err(|error| format!("{}: {}", value, error))
+            };
+            match arg.as_str() {
+                "--ascii" => parsed.ascii = true,
+                "--stride" => parsed.options.stride = number(value()?)?.max(1),
+                "--from" => parsed.options.from = number(value()?)?,
+                "--to" => parsed.options.to = Some(number(value()?)?),
+                "--fps" => {
+                    let fps = number(value()?)?.max(1);
+                    parsed.options.frame_time = Duration::from_secs_f64(1. / fps as f64);
+                }
+                "--cell" => parsed.options.cell_size = number(value()?)?.max(1) as u32,
+                "--theme" => parsed.theme = Some(value()?),
+                "--out" => parsed.out = Some(value()?.into()),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => parsed.file = arg.into(),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let replay = Replay::load(&args.file)
+        .map_err(|error| format!("Failed to read {}: {}", args.file.display(), error))?;
+    let output = if args.ascii {
+        replay.to_ascii(&args.options)
+    } else {
+        let theme = args.theme.unwrap_or_else(|| Settings::load().theme);
+        let palette = Palette::new(Themes::load().get(&theme));
+        replay.to_svg(&palette, &args.options)
+    };
+    match args.out {
+        Some(path) => std::fs::write(&path, output)
+            .map_err(|error| format!("Failed to write {}: {}", path.display(), error)),
+        None => {
+            print!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
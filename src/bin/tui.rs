@@ -0,0 +1,392 @@
+// Terminal front-end, for playing over SSH. Runs the Classic mode through SnakeEnv with the rules
+// picked in the settings, paced like the game, and draws the board with colored blocks. Build it
+// with `--features tui`.
+
+use std::io::{Stdout, Write, stdout};
+use std::time::{Duration, Instant};
+
+use bevy::math::IVec2;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use game::snake::{
+    Action, Arena, EndCause, EnvConfig, GameRules, Localization, POINTS_PER_APPLE, RulesPresets,
+    Settings, SnakeEnv, SpeedChanged,
+};
+
+/// The HUD and the message line below the board
+const TEXT_ROWS: u16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tile {
+    Empty,
+    Wall,
+    Head,
+    Body,
+    Apple,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Tile {
+    fn color(self: &Self) -> Color {
+        match self {
+            Tile::Empty => Color::Reset,
+            Tile::Wall => Color::DarkGrey,
+            Tile::Head => Color::Green,
+            Tile::Body => Color::DarkGreen,
+            Tile::Apple => Color::Red,
+        }
+    }
+}
+
+/// How cells map to characters, picked from the terminal size
+#[derive(Clone, Copy)]
+enum Layout {
+    /// Two columns per cell, one row each, which looks square on most fonts
+    Wide,
+    /// One column per cell, two rows per line drawn with half blocks
+    Compact,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Layout {
+    /// `side` is the board side in cells, border walls included
+    fn pick(columns: u16, rows: u16, side: u16) -> Option<Self> {
+        if columns >= side * 2 && rows >= side + TEXT_ROWS {
+            Some(Layout::Wide)
+        } else if columns >= side && rows >= side.div_ceil(2) + TEXT_ROWS {
+            Some(Layout::Compact)
+        } else {
+            None
+        }
+    }
+
+    fn size(self: &Self, side: u16) -> (u16, u16) {
+        match self {
+            Layout::Wide => (side * 2, side),
+            Layout::Compact => (side, side.div_ceil(2)),
+        }
+    }
+}
+
+struct Game {
+    env: SnakeEnv,
+    multiplier: f64,
+    pending: Action,
+    hz: f64,
+    next_move: Instant,
+    played: Duration,
+    paused: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Game {
+    fn new(multiplier: f64, rules: GameRules) -> Self {
+        let mut env = SnakeEnv::new(EnvConfig {
+            rules,
+            arena: Arena::new(&rules),
+            max_idle_steps: None,
+            ..Default::default()
+        });
+        env.reset(rand::random());
+        let hz = env.arena().start_hz * multiplier;
+        Self {
+            env,
+            multiplier,
+            pending: Action::Straight,
+            hz,
+            next_move: Instant::now(),
+            played: Duration::ZERO,
+            paused: false,
+        }
+    }
+
+    /// Board side in cells, border walls included
+    fn side(self: &Self) -> u16 {
+        self.env.grid_side() as u16
+    }
+
+    fn is_over(self: &Self) -> bool {
+        self.env.info().end.is_some()
+    }
+
+    /// Same as the game, turning back onto the body does nothing. Ignoring it here keeps an
+    /// earlier key of the same move.
+    fn turn(self: &mut Self, action: Action, direction: IVec2) {
+        if direction != -self.env.direction() {
+            self.pending = action;
+        }
+    }
+
+    fn step(self: &mut Self) {
+        let apples = self.env.info().apples;
+        let (_, _, _, info) = self.env.step(self.pending);
+        self.pending = Action::Straight;
+        if info.apples > apples {
+            // The game picks the pace before the new segment is spawned
+            let parts = info.length - self.env.rules().growth;
+            self.hz = SpeedChanged::after_apple(
+                self.env.arena(),
+                self.env.rules(),
+                parts,
+                self.multiplier,
+            )
+            .hz;
+        }
+    }
+
+    /// Every tile of the board, top row first. Built in one pass over each kind of piece, later
+    /// ones drawn over earlier ones.
+    fn tiles(self: &Self) -> Vec<Vec<Tile>> {
+        let side = self.side() as usize;
+        let rules = self.env.rules();
+        let mut rows = vec![vec![Tile::Empty; side]; side];
+        let mut put = |cell: IVec2, tile: Tile| {
+            let (x, y) = (cell.x + 1, cell.y + 1);
+            if (0..side as i32).contains(&x) && (0..side as i32).contains(&y) {
+                rows[side - 1 - y as usize][x as usize] = tile;
+            }
+        };
+        for apple in self.env.apples() {
+            put(*apple, Tile::Apple);
+        }
+        let mut snake = self.env.snake();
+        let head = snake.next();
+        for segment in snake {
+            put(segment, Tile::Body);
+        }
+        if let Some(head) = head {
+            put(head, Tile::Head);
+        }
+        for obstacle in &self.env.arena().obstacles {
+            put(*obstacle, Tile::Wall);
+        }
+        for y in -1..=rules.side + 1 {
+            for x in -1..=rules.side + 1 {
+                let cell = IVec2::new(x, y);
+                if !rules.is_inside(cell) {
+                    put(cell, Tile::Wall);
+                }
+            }
+        }
+        rows
+    }
+}
+
+fn format_time(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn hud(game: &Game, localization: &Localization) -> String {
+    let info = game.env.info();
+    [
+        localization.format(
+            "hud.score",
+            &[&(info.apples * POINTS_PER_APPLE).to_string()],
+        ),
+        localization.format("hud.length", &[&info.length.to_string()]),
+        localization.format("hud.speed", &[&format!("{:.1}", game.hz)]),
+        localization.format("hud.time", &[&format_time(game.played.as_secs())]),
+    ]
+    .join("   ")
+}
+
+fn message(game: &Game, localization: &Localization) -> String {
+    let info = game.env.info();
+    let Some(end) = info.end else {
+        return localization.get(if game.paused {
+            "tui.paused"
+        } else {
+            "tui.help"
+        });
+    };
+    let (x, y) = info
+        .end_cell
+        .map_or((String::new(), String::new()), |cell| {
+            (cell.x.to_string(), cell.y.to_string())
+        });
+    let reason = match end {
+        EndCause::BoardFilled => localization.get("victory.message"),
+        EndCause::Wall => localization.format("gameover.cause.wall", &[&x, &y]),
+        EndCause::SelfBite => localization.format("gameover.cause.self_bite", &[&x, &y]),
+        EndCause::Obstacle => localization.format("gameover.cause.obstacle", &[&x, &y]),
+        EndCause::Timeout => localization.get("gameover.cause.timeout"),
+        EndCause::GoalReached => String::new(),
+    };
+    format!("{}. {}", reason, localization.get("tui.restart"))
+}
+
+fn draw(out: &mut Stdout, game: &Game, localization: &Localization) -> std::io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    queue!(out, ResetColor, terminal::Clear(terminal::ClearType::All))?;
+    let side = game.side();
+    let Some(layout) = Layout::pick(columns, rows, side) else {
+        let (width, height) = Layout::Compact.size(side);
+        let text = localization.format(
+            "tui.too_small",
+            &[&width.to_string(), &(height + TEXT_ROWS).to_string()],
+        );
+        queue!(out, cursor::MoveTo(0, 0), Print(text))?;
+        return out.flush();
+    };
+    let (width, height) = layout.size(side);
+    let left = (columns - width) / 2;
+    let top = (rows - height - TEXT_ROWS) / 2;
+    queue!(
+        out,
+        cursor::MoveTo(left, top),
+        Print(hud(game, localization))
+    )?;
+
+    let tiles = game.tiles();
+    let lines: Vec<Vec<(Tile, Tile)>> = match layout {
+        Layout::Wide => tiles
+            .iter()
+            .map(|row| row.iter().map(|tile| (*tile, *tile)).collect())
+            .collect(),
+        // Upper and lower halves of each character
+        Layout::Compact => tiles
+            .chunks(2)
+            .map(|pair| {
+                let lower = pair.get(1);
+                (0..pair[0].len())
+                    .map(|x| (pair[0][x], lower.map_or(Tile::Empty, |row| row[x])))
+                    .collect()
+            })
+            .collect(),
+    };
+    for (idx, line) in lines.iter().enumerate() {
+        queue!(out, cursor::MoveTo(left, top + 1 + idx as u16))?;
+        for (upper, lower) in line {
+            match layout {
+                Layout::Wide => queue!(out, SetBackgroundColor(upper.color()), Print("  "))?,
+                Layout::Compact => match (upper, lower) {
+                    (Tile::Empty, Tile::Empty) => queue!(out, ResetColor, Print(" "))?,
+                    (Tile::Empty, lower) => queue!(
+                        out,
+                        ResetColor,
+                        SetForegroundColor(lower.color()),
+                        Print("▄")
+                    )?,
+                    (upper, lower) => queue!(
+                        out,
+                        SetForegroundColor(upper.color()),
+                        SetBackgroundColor(lower.color()),
+                        Print("▀")
+                    )?,
+                },
+            }
+        }
+        queue!(out, ResetColor)?;
+    }
+    queue!(
+        out,
+        cursor::MoveTo(left, top + 1 + height),
+        Print(message(game, localization))
+    )?;
+    out.flush()
+}
+
+enum Input {
+    Quit,
+    Redraw,
+    None,
+}
+
+fn handle_key(game: &mut Game, key: KeyEvent) -> Input {
+    if key.kind != KeyEventKind::Press {
+        return Input::None;
+    }
+    let turn = match key.code {
+        KeyCode::Char('w') | KeyCode::Up => Some((Action::Up, IVec2::Y)),
+        KeyCode::Char('s') | KeyCode::Down => Some((Action::Down, IVec2::NEG_Y)),
+        KeyCode::Char('a') | KeyCode::Left => Some((Action::Left, IVec2::NEG_X)),
+        KeyCode::Char('d') | KeyCode::Right => Some((Action::Right, IVec2::X)),
+        _ => None,
+    };
+    if let Some((action, direction)) = turn {
+        game.turn(action, direction);
+        return Input::None;
+    }
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Input::Quit,
+        KeyCode::Char('q') | KeyCode::Esc => Input::Quit,
+        KeyCode::Char('p') if !game.is_over() => {
+            game.paused = !game.paused;
+            game.next_move = Instant::now();
+            Input::Redraw
+        }
+        KeyCode::Char('r') if game.is_over() => {
+            *game = Game::new(game.multiplier, *game.env.rules());
+            Input::Redraw
+        }
+        _ => Input::None,
+    }
+}
+
+fn run(out: &mut Stdout) -> std::io::Result<()> {
+    let settings = Settings::load();
+    let localization = Localization::load(settings.language);
+    let rules = RulesPresets::load().get(&settings.rules);
+    let mut game = Game::new(settings.difficulty.speed_multiplier(), rules);
+    let mut last_frame = Instant::now();
+    draw(out, &game, &localization)?;
+    loop {
+        let running = !game.paused && !game.is_over();
+        let timeout = if running {
+            game.next_move.saturating_duration_since(Instant::now())
+        } else {
+            Duration::from_millis(250)
+        };
+        let mut redraw = false;
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) => match handle_key(&mut game, key) {
+                    Input::Quit => return Ok(()),
+                    Input::Redraw => redraw = true,
+                    Input::None => {}
+                },
+                Event::Resize(_, _) => redraw = true,
+                _ => {}
+            }
+        }
+        let now = Instant::now();
+        if running {
+            game.played += now - last_frame;
+        }
+        last_frame = now;
+        if running && now >= game.next_move {
+            game.step();
+            game.next_move = now + Duration::from_secs_f64(1. / game.hz);
+            redraw = true;
+        }
+        if redraw {
+            draw(out, &game, &localization)?;
+        }
+    }
+}
+
+fn restore_terminal() {
+    let _ = execute!(
+        stdout(),
+        ResetColor,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    );
+    let _ = terminal::disable_raw_mode();
+}
+
+fn main() -> std::io::Result<()> {
+    // A panic would otherwise leave the terminal in raw mode
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+    let mut out = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = run(&mut out);
+    restore_terminal();
+    result
+}
@@ -1,18 +1,37 @@
 use bevy::prelude::*;
-mod snake;
+use game::snake;
 
 fn main() {
+    // Loaded before the window is created, so it already opens with the saved mode and size
+    let settings = snake::Settings::load();
     let mut app = App::new();
     app.add_plugins(DefaultPlugins.set(WindowPlugin {
-        primary_window: Some(Window {
-            title: "Snake".to_owned(),
-            ..default()
-        }),
+        primary_window: Some(settings.window()),
         ..default()
     }))
+    .insert_resource(settings)
+    .add_plugins(snake::SettingsPlugin)
+    .add_plugins(snake::LocalizationPlugin)
+    .add_plugins(snake::FocusPlugin)
+    .add_plugins(snake::MenuPlugin)
+    .add_plugins(snake::ThemePlugin)
+    .add_plugins(snake::RulesPlugin)
     .add_plugins(snake::EntrancePlugin)
+    .add_plugins(snake::LoadingPlugin)
     .add_plugins(snake::MainPlugin)
+    .add_plugins(snake::GameModePlugin)
     .add_plugins(snake::GameplayPlugin)
+    .add_plugins(snake::PausePlugin)
+    .add_plugins(snake::TimeAttackPlugin)
+    .add_plugins(snake::CampaignPlugin)
     .add_plugins(snake::GameOverPlugin)
+    .add_plugins(snake::VictoryPlugin)
+    .add_plugins(snake::ScorePlugin)
+    .add_plugins(snake::HudPlugin)
+    .add_plugins(snake::AchievementsPlugin)
+    .add_plugins(snake::StatsPlugin)
+    .add_plugins(snake::VersusPlugin)
+    .add_plugins(snake::RemotePlugin)
+    .add_plugins(snake::ReplayPlugin)
     .run();
 }
@@ -0,0 +1,404 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::gameplay::{SnakeHead, SpeedChanged};
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen};
+use super::rules::GameRules;
+use super::score::RunTime;
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Achievements::load())
+            .init_resource::<Toasts>()
+            .init_resource::<Streak>()
+            .add_menu(MenuId::Achievements, achievements_menu)
+            .add_systems(OnEnter(super::GameState::Gameplay), reset_streak)
+            .add_systems(
+                Update,
+                (track_turns, on_apple_eaten, check_length, on_speed_changed)
+                    .chain()
+                    .run_if(in_state(super::GameState::Gameplay)),
+            )
+            .add_systems(OnEnter(super::GameState::Gameover), on_game_over)
+            .add_systems(OnEnter(super::GameState::Victory), on_game_over)
+            .add_systems(Update, (save_achievements, show_toasts, animate_toasts));
+    }
+}
+
+const ACHIEVEMENTS_FILE: &str = "achievements.ron";
+const LONG_SNAKE: usize = 50;
+const FAST_HZ: f64 = 20.;
+const STRAIGHT_APPLES: u32 = 10;
+const BOARD_SHARE: f32 = 0.25;
+const MARATHON: Duration = Duration::from_secs(5 * 60);
+
+const TOAST_SLIDE: f32 = 0.3;
+const TOAST_DURATION: f32 = 3.;
+const TOAST_TOP: f32 = 70.;
+const TOAST_HIDDEN_TOP: f32 = -80.;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Achievement {
+    FirstBite,
+    LongSnake,
+    SpeedDemon,
+    MaxSpeed,
+    Straight,
+    QuarterBoard,
+    Marathon,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Achievement {
+    const ALL: [Achievement; 7] = [
+        Achievement::FirstBite,
+        Achievement::LongSnake,
+        Achievement::SpeedDemon,
+        Achievement::MaxSpeed,
+        Achievement::Straight,
+        Achievement::QuarterBoard,
+        Achievement::Marathon,
+    ];
+
+    /// Localization key prefix, the name and description live under `.name` and `.description`
+    fn key(self: &Self) -> &'static str {
+        match self {
+            Achievement::FirstBite => "achievement.first_bite",
+            Achievement::LongSnake => "achievement.long_snake",
+            Achievement::SpeedDemon => "achievement.speed_demon",
+            Achievement::MaxSpeed => "achievement.max_speed",
+            Achievement::Straight => "achievement.straight",
+            Achievement::QuarterBoard => "achievement.quarter_board",
+            Achievement::Marathon => "achievement.marathon",
+        }
+    }
+
+    fn name(self: &Self, localization: &Localization) -> String {
+        localization.get(&format!("{}.name", self.key()))
+    }
+
+    fn description(self: &Self, localization: &Localization) -> String {
+        localization.get(&format!("{}.description", self.key()))
+    }
+}
+
+/// Unlocked achievements, in the order they were unlocked.
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Achievements {
+    pub unlocked: Vec<Achievement>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Achievements {
+    fn load() -> Self {
+        super::storage::load_ron(ACHIEVEMENTS_FILE).unwrap_or_default()
+    }
+
+    fn save(self: &Self) {
+        super::storage::save_ron(ACHIEVEMENTS_FILE, self);
+    }
+
+    pub fn is_unlocked(self: &Self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Queues a toast the first time an achievement is unlocked
+    fn unlock(self: &mut Self, achievement: Achievement, toasts: &mut Toasts) {
+        if !self.is_unlocked(achievement) {
+            self.unlocked.push(achievement);
+            toasts.0.push_back(achievement);
+        }
+    }
+}
+
+/// Achievements waiting for their toast, shown one at a time.
+#[derive(Resource, Default)]
+struct Toasts(VecDeque<Achievement>);
+
+/// Apples eaten since the last turn.
+#[derive(Resource, Default)]
+struct Streak {
+    apples: u32,
+    direction: Option<Vec2>,
+}
+
+#[derive(Component)]
+struct Toast(Timer);
+
+fn reset_streak(mut streak: ResMut<Streak>) {
+    *streak = Streak::default();
+}
+
+fn track_turns(head: Option<Single<&SnakeHead>>, mut streak: ResMut<Streak>) {
+    let Some(head) = head else {
+        return;
+    };
+    let direction = head.direction();
+    if streak.direction.is_some_and(|last| last != direction) {
+        streak.apples = 0;
+    }
+    streak.direction = Some(direction);
+}
+
+fn on_apple_eaten(
+    mut apple_eaten_event: EventReader<super::AppleEaten>,
+    mut streak: ResMut<Streak>,
+    mut achievements: ResMut<Achievements>,
+    mut toasts: ResMut<Toasts>,
+) {
+    let eaten = apple_eaten_event.read().count() as u32;
+    if eaten == 0 {
+        return;
+    }
+    streak.apples += eaten;
+    achievements.unlock(Achievement::FirstBite, &mut toasts);
+    if streak.apples >= STRAIGHT_APPLES {
+        achievements.unlock(Achievement::Straight, &mut toasts);
+    }
+}
+
+fn check_length(
+    new_parts: Query<(), Added<super::SnakePart>>,
+    parts: Query<&super::SnakePart>,
+    rules: Res<GameRules>,
+    mut achievements: ResMut<Achievements>,
+    mut toasts: ResMut<Toasts>,
+) {
+    if new_parts.is_empty() {
+        return;
+    }
+    let length = parts.iter().count();
+    if length >= LONG_SNAKE {
+        achievements.unlock(Achievement::LongSnake, &mut toasts);
+    }
+    if length as f32 >= rules.board_cells() as f32 * BOARD_SHARE {
+        achievements.unlock(Achievement::QuarterBoard, &mut toasts);
+    }
+}
+
+fn on_speed_changed(
+    mut speed_changed_event: EventReader<SpeedChanged>,
+    mut achievements: ResMut<Achievements>,
+    mut toasts: ResMut<Toasts>,
+) {
+    for speed in speed_changed_event.read() {
+        if speed.hz >= FAST_HZ {
+            achievements.unlock(Achievement::SpeedDemon, &mut toasts);
+        }
+        if speed.at_max {
+            achievements.unlock(Achievement::MaxSpeed, &mut toasts);
+        }
+    }
+}
+
+fn on_game_over(
+    run_time: Res<RunTime>,
+    mut achievements: ResMut<Achievements>,
+    mut toasts: ResMut<Toasts>,
+) {
+    if run_time.0 >= MARATHON {
+        achievements.unlock(Achievement::Marathon, &mut toasts);
+    }
+}
+
+fn save_achievements(achievements: Res<Achievements>) {
+    if achievements.is_changed() && !achievements.is_added() {
+        achievements.save();
+    }
+}
+
+fn show_toasts(
+    mut commands: Commands,
+    mut toasts: ResMut<Toasts>,
+    shown: Query<(), With<Toast>>,
+    localization: Res<Localization>,
+) {
+    if !shown.is_empty() {
+        return;
+    }
+    let Some(achievement) = toasts.0.pop_front() else {
+        return;
+    };
+    commands.spawn((
+        Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.),
+            top: Val::Px(TOAST_HIDDEN_TOP),
+            padding: UiRect::all(Val::Px(12.)),
+            border: UiRect::all(Val::Px(3.)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BorderColor(Color::srgb(0.95, 0.8, 0.2)),
+        BorderRadius::all(Val::Px(8.)),
+        BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.9)),
+        GlobalZIndex(10),
+        children![
+            (
+                Text::new(localization.get("achievements.unlocked")),
+                TextFont::from_font_size(14.),
+                TextColor(Color::srgb(0.95, 0.8, 0.2)),
+            ),
+            (
+                Text::new(achievement.name(&localization)),
+                TextColor(Color::srgb(1., 1., 1.)),
+            ),
+        ],
+    ));
+}
+
+/// Slides the toast in from the top, holds it and slides it back out
+fn animate_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    toasts: Query<(Entity, &mut Toast, &mut Node)>,
+) {
+    for (entity, mut toast, mut node) in toasts {
+        toast.0.tick(time.delta());
+        let elapsed = toast.0.elapsed_secs();
+        let shown = if elapsed < TOAST_SLIDE {
+            elapsed / TOAST_SLIDE
+        } else if elapsed > TOAST_DURATION - TOAST_SLIDE {
+            (TOAST_DURATION - elapsed) / TOAST_SLIDE
+        } else {
+            1.
+        };
+        node.top = Val::Px(TOAST_HIDDEN_TOP + (TOAST_TOP - TOAST_HIDDEN_TOP) * shown);
+        if toast.0.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn achievements_menu(world: &World) -> MenuScreen {
+    let achievements = world.resource::<Achievements>();
+    let localization = world.resource::<Localization>();
+    let mut items: Vec<MenuItem> = Achievement::ALL
+        .iter()
+        .map(|achievement| {
+            let key = if achievements.is_unlocked(*achievement) {
+                "achievements.entry.unlocked"
+            } else {
+                "achievements.entry.locked"
+            };
+            MenuItem::Label(localization.format(
+                key,
+                &[
+                    &achievement.name(localization),
+                    &achievement.description(localization),
+                ],
+            ))
+        })
+        .collect();
+    items.push(MenuItem::Label(localization.format(
+        "achievements.progress",
+        &[
+            &achievements.unlocked.len().to_string(),
+            &Achievement::ALL.len().to_string(),
+        ],
+    )));
+    items.push(MenuItem::Button {
+        label: "common.back",
+        action: MenuAction::Back,
+    });
+    MenuScreen {
+        title: Some("achievements.title"),
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{AppleEaten, SnakePart};
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn app(rules: GameRules) -> App {
+        let mut app = App::new();
+        app.add_event::<AppleEaten>()
+            .add_event::<SpeedChanged>()
+            .insert_resource(rules)
+            .init_resource::<Achievements>()
+            .init_resource::<Toasts>()
+            .init_resource::<Streak>()
+            .init_resource::<RunTime>()
+            .add_systems(
+                Update,
+                (track_turns, on_apple_eaten, check_length, on_speed_changed).chain(),
+            );
+        app
+    }
+
+    fn eat(app: &mut App, apples: u32) {
+        for _ in 0..apples {
+            app.world_mut().send_event(AppleEaten {
+                entity: Entity::PLACEHOLDER,
+                cell: IVec2::ZERO,
+            });
+        }
+        app.update();
+    }
+
+    fn unlocked(app: &App, achievement: Achievement) -> bool {
+        app.world()
+            .resource::<Achievements>()
+            .is_unlocked(achievement)
+    }
+
+    #[test]
+    fn straight_streak_resets_on_a_turn() {
+        let mut app = app(GameRules::DEFAULT);
+        let head = app.world_mut().spawn(SnakeHead::new(Vec2::X)).id();
+        eat(&mut app, STRAIGHT_APPLES - 1);
+        assert!(unlocked(&app, Achievement::FirstBite));
+        assert!(!unlocked(&app, Achievement::Straight));
+
+        app.world_mut()
+            .entity_mut(head)
+            .insert(SnakeHead::new(Vec2::Y));
+        eat(&mut app, STRAIGHT_APPLES - 1);
+        assert!(!unlocked(&app, Achievement::Straight));
+        eat(&mut app, 1);
+        assert!(unlocked(&app, Achievement::Straight));
+        assert_eq!(
+            app.world().resource::<Toasts>().0,
+            [Achievement::FirstBite, Achievement::Straight]
+        );
+    }
+
+    #[test]
+    fn quarter_board_follows_the_board_size() {
+        let rules = GameRules {
+            side: 3,
+            ..GameRules::DEFAULT
+        };
+        let mut app = app(rules);
+        for _ in 0..3 {
+            app.world_mut().spawn(SnakePart);
+        }
+        app.update();
+        assert!(!unlocked(&app, Achievement::QuarterBoard));
+        app.world_mut().spawn(SnakePart);
+        app.update();
+        assert!(unlocked(&app, Achievement::QuarterBoard));
+        assert!(!unlocked(&app, Achievement::LongSnake));
+    }
+
+    #[test]
+    fn marathon_needs_the_full_run_time() {
+        let mut app = app(GameRules::DEFAULT);
+        app.world_mut().resource_mut::<RunTime>().0 = MARATHON - Duration::from_millis(1);
+        app.world_mut().run_system_once(on_game_over).unwrap();
+        assert!(!unlocked(&app, Achievement::Marathon));
+        app.world_mut().resource_mut::<RunTime>().0 = MARATHON;
+        app.world_mut().run_system_once(on_game_over).unwrap();
+        assert!(unlocked(&app, Achievement::Marathon));
+    }
+}
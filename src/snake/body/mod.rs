@@ -0,0 +1,307 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    pub entity: Entity,
+    pub cell: IVec2,
+}
+
+/// Segments of a snake in order, from the head (index 0) to the tail. Lives on the head entity.
+///
+/// Moving relocates the tail segment right behind the head, so no other segment has to move.
+/// As segments don't keep their index, body patterns are painted with a skin index that stays
+/// consecutive along the body: it starts at `skin_base` behind the head and shifts on every move.
+#[derive(Component, Clone)]
+pub struct SnakeBody {
+    segments: VecDeque<Segment>,
+    /// Cell left behind by the last move, where the body grows into
+    vacated: IVec2,
+    skin_base: usize,
+}
+
+/// High enough to never run out of moves, decrementing once per move
+const SKIN_START: usize = 1 << 30;
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl SnakeBody {
+    pub fn new(head: Entity, cell: IVec2) -> Self {
+        Self {
+            segments: VecDeque::from([Segment { entity: head, cell }]),
+            vacated: cell,
+            skin_base: SKIN_START,
+        }
+    }
+
+    pub fn len(self: &Self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn head(self: &Self) -> Segment {
+        self.segments[0]
+    }
+
+    pub fn tail(self: &Self) -> Segment {
+        self.segments[self.segments.len() - 1]
+    }
+
+    pub fn get(self: &Self, idx: usize) -> Option<Segment> {
+        self.segments.get(idx).copied()
+    }
+
+    pub fn iter(self: &Self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter()
+    }
+
+    pub fn vacated(self: &Self) -> IVec2 {
+        self.vacated
+    }
+
+    /// Skin index of the segment at `idx`, the head always gets 0
+    pub fn skin(self: &Self, idx: usize) -> usize {
+        if idx == 0 {
+            return 0;
+        }
+        self.skin_base + idx
+    }
+
+    /// Moves the head to `next`. Returns the segment relocated behind the head, if any, the
+    /// vacated cell is updated either way.
+    pub fn advance(self: &mut Self, next: IVec2) -> Option<Segment> {
+        let previous = self.segments[0].cell;
+        self.segments[0].cell = next;
+        if self.segments.len() == 1 {
+            self.vacated = previous;
+            return None;
+        }
+        let mut tail = self.segments.pop_back()?;
+        self.vacated = tail.cell;
+        tail.cell = previous;
+        // Next to the front, so VecDeque only shifts the head
+        self.segments.insert(1, tail);
+        self.skin_base = self.skin_base.saturating_sub(1);
+        Some(tail)
+    }
+
+    /// Appends a segment on the vacated cell
+    pub fn grow(self: &mut Self, entity: Entity) -> Segment {
+        let segment = Segment {
+            entity,
+            cell: self.vacated,
+        };
+        self.segments.push_back(segment);
+        segment
+    }
+
+    /// Appends a segment on `cell`, for bodies laid out before the first move
+    pub fn push(self: &mut Self, entity: Entity, cell: IVec2) -> Segment {
+        let segment = Segment { entity, cell };
+        self.segments.push_back(segment);
+        self.vacated = cell;
+        segment
+    }
+
+    /// Removes the tail, the head is never removed. The caller frees its cell and despawns it.
+    pub fn shrink(self: &mut Self) -> Option<Segment> {
+        if self.segments.len() == 1 {
+            return None;
+        }
+        let tail = self.segments.pop_back()?;
+        self.vacated = tail.cell;
+        Some(tail)
+    }
+
+    /// Cuts the body at `idx`, returning that segment and every one behind it, from the front.
+    /// The head is never cut, so 0 cuts right behind it.
+    pub fn cut(self: &mut Self, idx: usize) -> Vec<Segment> {
+        let idx = idx.max(1);
+        if idx >= self.segments.len() {
+            return Vec::new();
+        }
+        let removed: Vec<Segment> = self.segments.split_off(idx).into();
+        self.vacated = removed[0].cell;
+        removed
+    }
+
+    /// Removes a single segment, the ones behind it keep their cells. The head is never removed.
+    pub fn remove(self: &mut Self, idx: usize) -> Option<Segment> {
+        if idx == 0 {
+            return None;
+        }
+        self.segments.remove(idx)
+    }
+
+    /// Puts every segment on the same cell, the body unfolds from there as the head moves
+    pub fn stack(self: &mut Self, cell: IVec2) {
+        for segment in self.segments.iter_mut() {
+            segment.cell = cell;
+        }
+        self.vacated = cell;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(body: &SnakeBody) -> Vec<IVec2> {
+        body.iter().map(|segment| segment.cell).collect()
+    }
+
+    /// Head on (2, 0), body on (1, 0) and (0, 0), heading right
+    fn body() -> SnakeBody {
+        let mut body = SnakeBody::new(Entity::from_raw(0), IVec2::new(2, 0));
+        body.push(Entity::from_raw(1), IVec2::new(1, 0));
+        body.push(Entity::from_raw(2), IVec2::new(0, 0));
+        body
+    }
+
+    #[test]
+    fn lone_head_advances_alone() {
+        let mut body = SnakeBody::new(Entity::from_raw(0), IVec2::new(3, 3));
+        assert!(body.advance(IVec2::new(3, 4)).is_none());
+        assert_eq!(cells(&body), [IVec2::new(3, 4)]);
+        assert_eq!(body.vacated(), IVec2::new(3, 3));
+    }
+
+    #[test]
+    fn advance_moves_the_tail_behind_the_head() {
+        let mut body = body();
+        let moved = body.advance(IVec2::new(3, 0)).unwrap();
+        assert_eq!(moved.entity, Entity::from_raw(2));
+        assert_eq!(moved.cell, IVec2::new(2, 0));
+        assert_eq!(
+            cells(&body),
+            [IVec2::new(3, 0), IVec2::new(2, 0), IVec2::new(1, 0)]
+        );
+        assert_eq!(body.vacated(), IVec2::new(0, 0));
+        assert_eq!(body.head().entity, Entity::from_raw(0));
+        assert_eq!(body.tail().entity, Entity::from_raw(1));
+    }
+
+    #[test]
+    fn grow_fills_the_vacated_cell() {
+        let mut body = body();
+        body.advance(IVec2::new(2, 1));
+        let segment = body.grow(Entity::from_raw(3));
+        assert_eq!(segment.cell, IVec2::new(0, 0));
+        assert_eq!(body.len(), 4);
+        assert_eq!(body.tail().entity, Entity::from_raw(3));
+        assert_eq!(
+            body.get(3).map(|segment| segment.cell),
+            Some(IVec2::new(0, 0))
+        );
+        assert!(body.get(4).is_none());
+    }
+
+    #[test]
+    fn push_lays_out_and_moves_the_vacated_cell() {
+        let body = body();
+        assert_eq!(body.len(), 3);
+        assert_eq!(body.vacated(), IVec2::new(0, 0));
+    }
+
+    #[test]
+    fn stacked_body_unfolds_behind_the_head() {
+        let mut body = body();
+        body.stack(IVec2::new(5, 5));
+        assert!(cells(&body).iter().all(|cell| *cell == IVec2::new(5, 5)));
+        assert_eq!(body.vacated(), IVec2::new(5, 5));
+
+        body.advance(IVec2::new(5, 6));
+        assert_eq!(body.vacated(), IVec2::new(5, 5));
+        body.advance(IVec2::new(5, 7));
+        assert_eq!(
+            cells(&body),
+            [IVec2::new(5, 7), IVec2::new(5, 6), IVec2::new(5, 5)]
+        );
+    }
+
+    #[test]
+    fn skin_follows_the_segment_not_its_index() {
+        let mut body = body();
+        let skin = |body: &SnakeBody, entity: u32| {
+            let idx = body
+                .iter()
+                .position(|segment| segment.entity == Entity::from_raw(entity))
+                .unwrap();
+            body.skin(idx)
+        };
+        let before = skin(&body, 1);
+        body.advance(IVec2::new(3, 0));
+        assert_eq!(skin(&body, 1), before);
+        assert_eq!(body.skin(0), 0);
+    }
+
+    fn entities(body: &SnakeBody) -> Vec<u32> {
+        body.iter().map(|segment| segment.entity.index()).collect()
+    }
+
+    #[test]
+    fn shrink_drops_the_tail_but_never_the_head() {
+        let mut body = body();
+        let tail = body.shrink().unwrap();
+        assert_eq!(tail.entity, Entity::from_raw(2));
+        assert_eq!(body.vacated(), IVec2::new(0, 0));
+        assert_eq!(entities(&body), [0, 1]);
+        assert_eq!(body.shrink().unwrap().entity, Entity::from_raw(1));
+        assert_eq!(body.vacated(), IVec2::new(1, 0));
+        assert!(body.shrink().is_none());
+        assert_eq!(entities(&body), [0]);
+    }
+
+    #[test]
+    fn cut_at_the_head_keeps_it() {
+        let mut body = body();
+        let removed = body.cut(0);
+        assert_eq!(
+            removed
+                .iter()
+                .map(|segment| segment.entity.index())
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(entities(&body), [0]);
+        assert_eq!(body.vacated(), IVec2::new(1, 0));
+    }
+
+    #[test]
+    fn cut_in_the_middle_and_at_the_tail() {
+        let mut body = body();
+        body.grow(Entity::from_raw(3));
+        let removed = body.cut(2);
+        assert_eq!(
+            removed
+                .iter()
+                .map(|segment| segment.entity.index())
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert_eq!(removed[0].cell, IVec2::new(0, 0));
+        assert_eq!(entities(&body), [0, 1]);
+        assert_eq!(body.vacated(), IVec2::new(0, 0));
+
+        let mut short = self::body();
+        let last = short.len() - 1;
+        let removed = short.cut(last);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].entity, Entity::from_raw(2));
+        assert_eq!(entities(&short), [0, 1]);
+        // Past the tail there is nothing to cut
+        assert!(short.cut(short.len()).is_empty());
+        assert_eq!(entities(&short), [0, 1]);
+    }
+
+    #[test]
+    fn remove_keeps_the_cells_of_the_segments_behind() {
+        let mut body = body();
+        assert!(body.remove(0).is_none());
+        let removed = body.remove(1).unwrap();
+        assert_eq!(removed.entity, Entity::from_raw(1));
+        assert_eq!(entities(&body), [0, 2]);
+        assert_eq!(cells(&body), [IVec2::new(2, 0), IVec2::new(0, 0)]);
+        assert!(body.remove(5).is_none());
+        assert_eq!(body.remove(1).unwrap().entity, Entity::from_raw(2));
+        assert_eq!(entities(&body), [0]);
+    }
+}
@@ -0,0 +1,453 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::GameMode;
+use super::gameplay::{Arena, EndCause, EndRun, GameEnd};
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen};
+use super::rules::GameRules;
+use super::score::RunTime;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        let campaign = Campaign::load();
+        let progress = CampaignProgress::load();
+        let current = progress.unlocked.min(campaign.0.len()).saturating_sub(1);
+        app.insert_resource(campaign)
+            .insert_resource(progress)
+            .insert_resource(CurrentLevel(current))
+            .init_resource::<LevelRun>()
+            .add_menu(MenuId::Campaign, campaign_menu)
+            .add_systems(
+                OnEnter(super::GameState::Gameplay),
+                (
+                    reset_level_run,
+                    spawn_goal_text.run_if(resource_equals(GameMode::Campaign)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (count_apples, check_goal.in_set(EndRun), update_goal_text)
+                    .chain()
+                    .run_if(in_state(super::GameState::Gameplay))
+                    .run_if(in_state(super::GameplayState::Running))
+                    .run_if(resource_equals(GameMode::Campaign)),
+            );
+    }
+}
+
+const CAMPAIGN_FILE: &str = "assets/campaign.ron";
+const PROGRESS_FILE: &str = "campaign.ron";
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+enum Goal {
+    /// Grow the snake up to the given length
+    Length(usize),
+    /// Eat `count` apples before `seconds` run out
+    Apples { count: u32, seconds: u64 },
+    /// Stay alive for the given seconds
+    Survive(u64),
+}
+
+/// A rectangle of obstacles, in board cells
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Block {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Level {
+    name: String,
+    goal: Goal,
+    /// Starting pace in moves per second, before the difficulty multiplier
+    speed: f64,
+    #[serde(default)]
+    obstacles: Vec<Block>,
+    /// Segments of the snake at the start, one when left out
+    #[serde(default)]
+    start_length: Option<usize>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Level {
+    fn arena(self: &Self) -> Arena {
+        let mut obstacles: Vec<IVec2> = self
+            .obstacles
+            .iter()
+            .flat_map(|block| {
+                (block.x..block.x + block.width).flat_map(move |x| {
+                    (block.y..block.y + block.height).map(move |y| IVec2::new(x, y))
+                })
+            })
+            .filter(|cell| GameRules::DEFAULT.is_inside(*cell))
+            .collect();
+        // Blocks may overlap
+        obstacles.sort_by_key(|cell| (cell.x, cell.y));
+        obstacles.dedup();
+        Arena {
+            obstacles,
+            start_hz: self.speed,
+            start_length: self.start_length.unwrap_or(GameRules::DEFAULT.start_length),
+        }
+    }
+
+    /// The same level with whatever would break it or end it on the first step brought back in
+    /// range, warning about each change
+    fn validated(mut self: Self) -> Self {
+        let rules = GameRules::DEFAULT;
+        if !self.speed.is_finite() || self.speed <= 0. {
+            warn!(
+                "Level {}: speed {} isn't a positive number, using {}",
+                self.name, self.speed, rules.speed.start_hz
+            );
+            self.speed = rules.speed.start_hz;
+        }
+        // The snake needs a free cell to move into, and one more to grow into
+        let free = rules.board_cells() - self.arena().obstacles.len();
+        let max_length = free.saturating_sub(2).max(1);
+        if let Some(length) = self.start_length
+            && !(1..=max_length).contains(&length)
+        {
+            let start_length = length.clamp(1, max_length);
+            warn!(
+                "Level {}: start_length {} is out of range, using {}",
+                self.name, length, start_length
+            );
+            self.start_length = Some(start_length);
+        }
+        let start_length = self.start_length.unwrap_or(rules.start_length);
+        match &mut self.goal {
+            Goal::Length(length) => {
+                let goal = (*length).clamp(start_length + 1, free.max(start_length + 1));
+                if goal != *length {
+                    warn!(
+                        "Level {}: goal length {} is out of range, using {}",
+                        self.name, length, goal
+                    );
+                    *length = goal;
+                }
+            }
+            Goal::Apples { count, seconds } => {
+                if *count == 0 {
+                    warn!("Level {}: apple count can't be 0, using 1", self.name);
+                    *count = 1;
+                }
+                if *seconds == 0 {
+                    warn!("Level {}: apple seconds can't be 0, using 1", self.name);
+                    *seconds = 1;
+                }
+            }
+            Goal::Survive(seconds) => {
+                if *seconds == 0 {
+                    warn!("Level {}: survive seconds can't be 0, using 1", self.name);
+                    *seconds = 1;
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Every level, in the order they are unlocked.
+#[derive(Resource)]
+pub struct Campaign(Vec<Level>);
+
+impl Campaign {
+    fn load() -> Self {
+        let path = FileAssetReader::get_base_path().join(CAMPAIGN_FILE);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| Self::parse(&content));
+        parsed.unwrap_or_else(|error| {
+            warn!("Failed to load {}: {}", path.display(), error);
+            Self(Vec::new())
+        })
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let levels = ron::from_str::<Vec<Level>>(content).map_err(|e| e.to_string())?;
+        Ok(Self(levels.into_iter().map(Level::validated).collect()))
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    /// How many levels can be played, the first one is always unlocked
+    pub unlocked: usize,
+}
+
+impl Default for CampaignProgress {
+    fn default() -> Self {
+        Self { unlocked: 1 }
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl CampaignProgress {
+    fn load() -> Self {
+        super::storage::load_ron(PROGRESS_FILE).unwrap_or_default()
+    }
+
+    fn save(self: &Self) {
+        super::storage::save_ron(PROGRESS_FILE, self);
+    }
+}
+
+/// Level picked in the menu, or being played.
+#[derive(Resource)]
+struct CurrentLevel(usize);
+
+#[derive(Resource, Default)]
+struct LevelRun {
+    apples: u32,
+    cleared: bool,
+}
+
+#[derive(Component)]
+struct GoalText;
+
+pub fn start_level(world: &mut World, idx: usize) {
+    let Some(level) = world.resource::<Campaign>().0.get(idx) else {
+        return;
+    };
+    let arena = level.arena();
+    // Levels are laid out for the default rules, whatever preset is picked
+    world.insert_resource(GameRules::DEFAULT);
+    world.insert_resource(arena);
+    world.insert_resource(CurrentLevel(idx));
+    world.insert_resource(GameMode::Campaign);
+    world
+        .resource_mut::<NextState<super::GameState>>()
+        .set(super::GameState::Gameplay);
+}
+
+pub fn current_level(world: &World) -> usize {
+    world.resource::<CurrentLevel>().0
+}
+
+/// Whether the last level played was beaten
+pub fn level_cleared(world: &World) -> bool {
+    world.resource::<LevelRun>().cleared
+}
+
+/// The level unlocked by beating the last one, if there is any left
+pub fn next_level(world: &World) -> Option<usize> {
+    let next = current_level(world) + 1;
+    (level_cleared(world) && next < world.resource::<Campaign>().0.len()).then_some(next)
+}
+
+fn campaign_menu(_world: &World) -> MenuScreen {
+    MenuScreen {
+        title: Some("modes.campaign"),
+        items: vec![
+            MenuItem::Choice {
+                label: "campaign.level",
+                options: |world| {
+                    let unlocked = world.resource::<CampaignProgress>().unlocked;
+                    world
+                        .resource::<Campaign>()
+                        .0
+                        .iter()
+                        .take(unlocked)
+                        .enumerate()
+                        .map(|(idx, level)| format!("{}. {}", idx + 1, level.name))
+                        .collect()
+                },
+                get: current_level,
+                set: |world, idx| world.resource_mut::<CurrentLevel>().0 = idx,
+            },
+            MenuItem::Button {
+                label: "campaign.start",
+                action: MenuAction::Run(|world| start_level(world, current_level(world))),
+            },
+            MenuItem::Button {
+                label: "common.back",
+                action: MenuAction::Back,
+            },
+        ],
+    }
+}
+
+fn reset_level_run(mut level_run: ResMut<LevelRun>) {
+    *level_run = LevelRun::default();
+}
+
+fn spawn_goal_text(mut commands: Commands) {
+    commands.spawn((
+        super::hud::Hud,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            bottom: Val::Px(10.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            GoalText,
+            Text::default(),
+            TextColor(Color::srgb(1., 1., 1.)),
+            TextShadow::default(),
+        )],
+    ));
+}
+
+fn count_apples(
+    mut apple_eaten_event: EventReader<super::AppleEaten>,
+    mut level_run: ResMut<LevelRun>,
+) {
+    level_run.apples += apple_eaten_event.read().count() as u32;
+}
+
+fn check_goal(
+    campaign: Res<Campaign>,
+    current: Res<CurrentLevel>,
+    mut progress: ResMut<CampaignProgress>,
+    mut level_run: ResMut<LevelRun>,
+    parts: Query<&super::SnakePart>,
+    run_time: Res<RunTime>,
+    mut game_end_event: EventWriter<GameEnd>,
+) {
+    let Some(level) = campaign.0.get(current.0) else {
+        return;
+    };
+    let (cleared, failed) = match level.goal {
+        Goal::Length(length) => (parts.iter().count() >= length, false),
+        Goal::Apples { count, seconds } => (
+            level_run.apples >= count,
+            run_time.0 >= Duration::from_secs(seconds),
+        ),
+        Goal::Survive(seconds) => (run_time.0 >= Duration::from_secs(seconds), false),
+    };
+    if cleared {
+        level_run.cleared = true;
+        let unlocked = (current.0 + 2).min(campaign.0.len());
+        if unlocked > progress.unlocked {
+            progress.unlocked = unlocked;
+            progress.save();
+        }
+        game_end_event.write(GameEnd::new(EndCause::GoalReached));
+    } else if failed {
+        game_end_event.write(GameEnd::new(EndCause::Timeout));
+    }
+}
+
+fn update_goal_text(
+    mut text: Single<&mut Text, With<GoalText>>,
+    campaign: Res<Campaign>,
+    current: Res<CurrentLevel>,
+    level_run: Res<LevelRun>,
+    parts: Query<&super::SnakePart>,
+    run_time: Res<RunTime>,
+    localization: Res<Localization>,
+) {
+    let Some(level) = campaign.0.get(current.0) else {
+        return;
+    };
+    let value = match level.goal {
+        Goal::Length(length) => localization.format(
+            "goal.length",
+            &[&length.to_string(), &parts.iter().count().to_string()],
+        ),
+        Goal::Apples { count, seconds } => localization.format(
+            "goal.apples",
+            &[
+                &count.to_string(),
+                &seconds.to_string(),
+                &level_run.apples.to_string(),
+            ],
+        ),
+        Goal::Survive(seconds) => localization.format(
+            "goal.survive",
+            &[&seconds.to_string(), &run_time.0.as_secs().to_string()],
+        ),
+    };
+    if text.0 != value {
+        text.0 = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_level(goal: Goal, speed: f64, start_length: Option<usize>) -> Level {
+        Level {
+            name: "test".to_owned(),
+            goal,
+            speed,
+            obstacles: Vec::new(),
+            start_length,
+        }
+    }
+
+    #[test]
+    fn shipped_campaign_parses_and_is_valid() {
+        let campaign = Campaign::parse(include_str!("../../../assets/campaign.ron")).unwrap();
+        assert!(!campaign.0.is_empty());
+        for level in &campaign.0 {
+            let validated = level.clone().validated();
+            assert_eq!(validated.speed, level.speed, "{}", level.name);
+            assert_eq!(validated.start_length, level.start_length, "{}", level.name);
+            assert!(level.arena().start_hz > 0.);
+        }
+    }
+
+    #[test]
+    fn broken_levels_are_brought_back_in_range() {
+        let cells = GameRules::DEFAULT.board_cells();
+        let level = test_level(Goal::Length(2), f64::NAN, Some(0)).validated();
+        assert_eq!(level.speed, GameRules::DEFAULT.speed.start_hz);
+        assert_eq!(level.start_length, Some(1));
+        assert!(matches!(level.goal, Goal::Length(2)));
+
+        let level = test_level(Goal::Length(cells * 2), -3., Some(cells)).validated();
+        assert_eq!(level.speed, GameRules::DEFAULT.speed.start_hz);
+        assert_eq!(level.start_length, Some(cells - 2));
+        assert!(matches!(level.goal, Goal::Length(length) if length == cells));
+
+        let level = test_level(Goal::Length(5), 8., Some(5)).validated();
+        assert!(matches!(level.goal, Goal::Length(6)));
+
+        let level = test_level(
+            Goal::Apples {
+                count: 0,
+                seconds: 0,
+            },
+            0.,
+            None,
+        )
+        .validated();
+        assert_eq!(level.speed, GameRules::DEFAULT.speed.start_hz);
+        assert!(matches!(
+            level.goal,
+            Goal::Apples {
+                count: 1,
+                seconds: 1
+            }
+        ));
+        let level = test_level(Goal::Survive(0), 8., None).validated();
+        assert!(matches!(level.goal, Goal::Survive(1)));
+    }
+
+    #[test]
+    fn obstacles_count_against_the_length_goal() {
+        let mut level = test_level(Goal::Length(usize::MAX), 8., None);
+        level.obstacles.push(Block {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 10,
+        });
+        let level = level.validated();
+        let free = GameRules::DEFAULT.board_cells() - 100;
+        assert!(matches!(level.goal, Goal::Length(length) if length == free));
+    }
+}
@@ -1,17 +1,20 @@
+use bevy::asset::io::file::FileAssetReader;
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::time::Duration;
 pub struct EntrancePlugin;
 
 impl Plugin for EntrancePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_state(super::GameState::Main)
-            .insert_resource(FadeTimer(Timer::new(
-                Duration::from_secs(3),
-                TimerMode::Repeating,
-            )))
+        app.init_state::<super::GameState>()
+            .insert_resource(EntranceConfig::load())
+            .insert_resource(FadeTimer {
+                timer: Timer::default(),
+                logo: 0,
+            })
             .add_systems(
                 OnEnter(super::GameState::Entrance),
-                (spawn_ui, spawn_camera),
+                (spawn_ui, spawn_camera, start_entrance),
             )
             .add_systems(
                 Update,
@@ -21,7 +24,7 @@ impl Plugin for EntrancePlugin {
             )
             .add_systems(
                 Update,
-                render_entrance.run_if(in_state(super::GameState::Entrance)),
+                (render_entrance, skip_entrance).run_if(in_state(super::GameState::Entrance)),
             )
             .add_systems(
                 OnExit(super::GameState::Entrance),
@@ -30,64 +33,173 @@ impl Plugin for EntrancePlugin {
     }
 }
 
+const CONFIG_PATH: &str = "assets/entrance.ron";
+
+/// Splash sequence, read from assets/entrance.ron. Each logo fades in, stays on screen and then
+/// fades out before the next one starts. Durations are in seconds.
+#[derive(Resource, Deserialize)]
+#[serde(default)]
+struct EntranceConfig {
+    enabled: bool,
+    /// Paths relative to the assets folder
+    logos: Vec<String>,
+    fade_in: f32,
+    hold: f32,
+    fade_out: f32,
+}
+
+impl Default for EntranceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            logos: vec!["tupiniquim_logo.png".to_owned(), "bevy_logo.png".to_owned()],
+            fade_in: 0.8,
+            hold: 1.2,
+            fade_out: 0.8,
+        }
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl EntranceConfig {
+    fn load() -> Self {
+        let path = FileAssetReader::get_base_path().join(CONFIG_PATH);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            warn!("Missing {}, using the default entrance", path.display());
+            return Self::default();
+        };
+        ron::from_str::<Self>(&content)
+            .unwrap_or_else(|error| {
+                warn!("Failed to parse {}: {}", path.display(), error);
+                Self::default()
+            })
+            .validated()
+    }
+
+    /// The same config with every duration a finite number of seconds, at least 0
+    fn validated(mut self: Self) -> Self {
+        for (field, value) in [
+            ("fade_in", &mut self.fade_in),
+            ("hold", &mut self.hold),
+            ("fade_out", &mut self.fade_out),
+        ] {
+            if !value.is_finite() || *value < 0. {
+                warn!("Entrance: {} {} isn't a duration, using 0", field, value);
+                *value = 0.;
+            }
+        }
+        self
+    }
+
+    fn logo_duration(self: &Self) -> Duration {
+        Duration::from_secs_f32(self.fade_in + self.hold + self.fade_out)
+    }
+
+    /// Opacity of a logo that has been on screen for `elapsed` seconds
+    fn alpha(self: &Self, elapsed: f32) -> f32 {
+        if elapsed < self.fade_in {
+            elapsed / self.fade_in
+        } else if elapsed < self.fade_in + self.hold {
+            1.
+        } else {
+            let fading = elapsed - self.fade_in - self.hold;
+            1. - (fading / self.fade_out.max(f32::EPSILON)).min(1.)
+        }
+    }
+}
+
+/// Times the logo currently being shown
 #[derive(Resource)]
-struct FadeTimer(Timer);
-
-fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands
-        .spawn((Node {
-            position_type: PositionType::Relative,
-            width: Val::Percent(30.),
-            height: Val::Percent(30.),
-            left: Val::Percent(30.),
-            top: Val::Percent(30.),
-            ..default()
-        },))
-        .with_children(|parent| {
-            parent.spawn(ImageNode {
-                image: asset_server.load("tupiniquim_logo.png"),
-                ..Default::default()
-            });
-        });
-
-    commands
-        .spawn((Node {
-            position_type: PositionType::Relative,
-            width: Val::Percent(30.),
-            height: Val::Percent(30.),
-            left: Val::Percent(50.),
-            top: Val::Percent(30.),
-            ..default()
-        },))
-        .with_children(|parent| {
-            parent.spawn(ImageNode {
-                image: asset_server.load("bevy_logo.png"),
-                ..Default::default()
+struct FadeTimer {
+    timer: Timer,
+    logo: usize,
+}
+
+#[derive(Component)]
+struct SplashLogo(usize);
+
+fn start_entrance(
+    config: Res<EntranceConfig>,
+    mut timer: ResMut<FadeTimer>,
+    mut next_state: ResMut<NextState<super::GameState>>,
+) {
+    if !config.enabled || config.logos.is_empty() {
+        next_state.set(super::GameState::Loading);
+        return;
+    }
+    timer.timer = Timer::new(config.logo_duration(), TimerMode::Once);
+    timer.logo = 0;
+}
+
+fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>, config: Res<EntranceConfig>) {
+    for (idx, logo) in config.logos.iter().enumerate() {
+        commands
+            .spawn((Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(30.),
+                height: Val::Percent(30.),
+                left: Val::Percent(35.),
+                top: Val::Percent(35.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },))
+            .with_children(|parent| {
+                parent.spawn((
+                    SplashLogo(idx),
+                    ImageNode {
+                        image: asset_server.load(logo),
+                        color: Color::WHITE.with_alpha(0.),
+                        ..Default::default()
+                    },
+                ));
             });
-        });
+    }
 }
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn(Camera2d::default());
+    commands.spawn(Camera2d);
 }
 
-fn tick_timer(time: ResMut<Time>, mut timer: ResMut<FadeTimer>) {
-    timer.0.tick(time.delta());
+fn tick_timer(time: Res<Time>, mut timer: ResMut<FadeTimer>) {
+    timer.timer.tick(time.delta());
 }
 
-const ENTRANCE_TIME: f32 = 3.;
-
 fn render_entrance(
-    time: ResMut<Time>,
-    timer: Res<FadeTimer>,
+    mut timer: ResMut<FadeTimer>,
+    config: Res<EntranceConfig>,
+    logos: Query<(&mut ImageNode, &SplashLogo)>,
+    mut next_state: ResMut<NextState<super::GameState>>,
+) {
+    if timer.timer.finished() {
+        timer.logo += 1;
+        if timer.logo >= config.logos.len() {
+            next_state.set(super::GameState::Loading);
+            return;
+        }
+        timer.timer.reset();
+    }
+    let alpha = config.alpha(timer.timer.elapsed_secs());
+    for (mut image, logo) in logos {
+        let logo_alpha = if logo.0 == timer.logo { alpha } else { 0. };
+        image.color.set_alpha(logo_alpha);
+    }
+}
+
+fn skip_entrance(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
     mut next_state: ResMut<NextState<super::GameState>>,
 ) {
-    if time.elapsed_secs() >= 3. {
-        next_state.set(super::GameState::Main);
+    if keys.get_just_pressed().next().is_some() || mouse.get_just_pressed().next().is_some() {
+        next_state.set(super::GameState::Loading);
     }
 }
 
-fn despawn_ui(mut commands: Commands, node: Query<Entity, With<Node>>) {
+#[allow(clippy::type_complexity)]
+fn despawn_ui(
+    mut commands: Commands,
+    node: Query<Entity, (With<Node>, Without<ChildOf>, Without<super::hud::Hud>)>,
+) {
     for node in node {
         commands.entity(node).despawn();
     }
@@ -98,3 +210,62 @@ fn despawn_camera(mut commands: Commands, camera: Query<Entity, With<Camera2d>>)
         commands.entity(camera).despawn();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn durations(fade_in: f32, hold: f32, fade_out: f32) -> EntranceConfig {
+        EntranceConfig {
+            fade_in,
+            hold,
+            fade_out,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn alpha_fades_in_holds_and_fades_out() {
+        let config = durations(1., 2., 1.);
+        assert_eq!(config.alpha(0.), 0.);
+        assert_eq!(config.alpha(0.5), 0.5);
+        assert_eq!(config.alpha(1.), 1.);
+        assert_eq!(config.alpha(2.5), 1.);
+        assert_eq!(config.alpha(3.5), 0.5);
+        assert_eq!(config.alpha(4.), 0.);
+        assert_eq!(config.alpha(10.), 0.);
+        assert_eq!(config.logo_duration(), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn alpha_without_fades_is_never_nan() {
+        let config = durations(0., 1., 0.);
+        assert_eq!(config.alpha(0.), 1.);
+        assert_eq!(config.alpha(0.5), 1.);
+        assert_eq!(config.alpha(1.), 1.);
+        assert_eq!(config.alpha(1.5), 0.);
+        let config = durations(0., 0., 0.);
+        assert_eq!(config.alpha(0.5), 0.);
+        assert_eq!(config.logo_duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn broken_durations_are_brought_back_in_range() {
+        let config = durations(-1., f32::NAN, f32::INFINITY).validated();
+        assert_eq!((config.fade_in, config.hold, config.fade_out), (0., 0., 0.));
+        assert_eq!(config.logo_duration(), Duration::ZERO);
+        let config = durations(0.8, 1.2, 0.8).validated();
+        assert_eq!(
+            (config.fade_in, config.hold, config.fade_out),
+            (0.8, 1.2, 0.8)
+        );
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        let config: EntranceConfig =
+            ron::from_str(include_str!("../../../assets/entrance.ron")).unwrap();
+        assert!(!config.logos.is_empty());
+        assert!(config.fade_in >= 0. && config.hold >= 0. && config.fade_out >= 0.);
+    }
+}
@@ -0,0 +1,749 @@
+use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool};
+
+use super::RngResource;
+use super::body::SnakeBody;
+use super::gameplay::{Arena, EndCause, SnakeHead, find_collision, find_spawn};
+use super::occupancy::{Occupancy, Occupant};
+use super::rules::GameRules;
+
+/// Gym style environment for training agents, running the Classic mode of the game on the same
+/// body and occupancy types, without any App, window or renderer. One step is one move.
+pub struct SnakeEnv {
+    config: EnvConfig,
+    /// Seed of the current episode
+    seed: u64,
+    rng: RngResource,
+    occupancy: Occupancy,
+    head: SnakeHead,
+    body: SnakeBody,
+    apples: Vec<IVec2>,
+    info: StepInfo,
+    /// Moves since the last apple
+    idle: u32,
+    done: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Keeps the current direction
+    Straight,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Action {
+    fn direction(self: &Self) -> Option<Vec2> {
+        match self {
+            Action::Straight => None,
+            Action::Up => Some(Vec2::Y),
+            Action::Down => Some(Vec2::NEG_Y),
+            Action::Left => Some(Vec2::NEG_X),
+            Action::Right => Some(Vec2::X),
+        }
+    }
+}
+
+/// Added up on every step, `step` is given on every move including the last one
+#[derive(Clone, Copy, Debug)]
+pub struct Rewards {
+    pub step: f32,
+    pub apple: f32,
+    pub death: f32,
+    /// Filling the board
+    pub victory: f32,
+    /// Running out of `max_idle_steps`
+    pub starved: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            step: 0.,
+            apple: 1.,
+            death: -1.,
+            victory: 10.,
+            starved: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ObservationKind {
+    /// One channel per kind of cell (head, body, apple, wall) over the board and its border,
+    /// shaped `[GRID_CHANNELS, grid_side, grid_side]` with rows going up from the bottom
+    #[default]
+    Grid,
+    /// `FEATURES` values: danger ahead, on the left and on the right, the direction one-hot
+    /// (up, down, left, right), where the nearest apple is (up, down, left, right), the head
+    /// position and the length, the last three normalized
+    Features,
+}
+
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    pub rules: GameRules,
+    /// Usually `Arena::new(&rules)` plus obstacles
+    pub arena: Arena,
+    pub rewards: Rewards,
+    pub observation: ObservationKind,
+    /// Ends the episode after this many moves without eating, so an agent can't loop forever
+    pub max_idle_steps: Option<u32>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            rules: GameRules::DEFAULT,
+            arena: Arena::default(),
+            rewards: Rewards::default(),
+            observation: ObservationKind::default(),
+            max_idle_steps: Some(GameRules::DEFAULT.board_cells() as u32),
+        }
+    }
+}
+
+pub const GRID_CHANNELS: usize = 4;
+pub const FEATURES: usize = 14;
+
+/// Flat values in row-major order, along with their shape
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub data: Vec<f32>,
+    pub shape: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StepInfo {
+    pub length: usize,
+    pub apples: u32,
+    pub steps: u32,
+    /// Why the episode ended, None when it goes on or was cut by `max_idle_steps`
+    pub end: Option<EndCause>,
+    /// Where the head ran into something
+    pub end_cell: Option<IVec2>,
+    pub truncated: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl SnakeEnv {
+    /// Panics when the arena has no free cell to spawn the snake on, and so does reset
+    pub fn new(config: EnvConfig) -> Self {
+        Self::start(config, 0)
+    }
+
+    /// Board of a new episode. The same seed and actions always play out the same.
+    fn start(config: EnvConfig, seed: u64) -> Self {
+        let rng = RngResource::seeded(seed);
+        let side = config.rules.side;
+        let mut occupancy = Occupancy::new(side);
+        for y in -1..=side + 1 {
+            for x in -1..=side + 1 {
+                if x < 0 || y < 0 || x > side || y > side {
+                    occupancy.set(IVec2::new(x, y), Some(Occupant::Wall(Entity::PLACEHOLDER)));
+                }
+            }
+        }
+        for obstacle in &config.arena.obstacles {
+            occupancy.set(*obstacle, Some(Occupant::Obstacle(Entity::PLACEHOLDER)));
+        }
+        let (cells, direction) = find_spawn(&occupancy, &rng, config.arena.start_length)
+            .expect("The arena leaves no free cell for the snake");
+        let mut body = SnakeBody::new(Entity::PLACEHOLDER, cells[0]);
+        occupancy.add_segment(cells[0]);
+        for cell in &cells[1..] {
+            body.push(Entity::PLACEHOLDER, *cell);
+            occupancy.add_segment(*cell);
+        }
+        let head = SnakeHead::new(direction.as_vec2());
+        let mut env = Self {
+            config,
+            seed,
+            rng,
+            occupancy,
+            head,
+            body,
+            apples: Vec::new(),
+            info: StepInfo {
+                length: cells.len(),
+                ..default()
+            },
+            idle: 0,
+            done: false,
+        };
+        env.spawn_apples();
+        env
+    }
+
+    pub fn seed(self: &Self) -> u64 {
+        self.seed
+    }
+
+    /// Cells of the snake, from the head to the tail
+    pub fn snake(self: &Self) -> impl Iterator<Item = IVec2> + '_ {
+        self.body.iter().map(|segment| segment.cell)
+    }
+
+    pub fn direction(self: &Self) -> IVec2 {
+        self.head.direction().as_ivec2()
+    }
+
+    pub fn apples(self: &Self) -> &[IVec2] {
+        &self.apples
+    }
+
+    pub fn arena(self: &Self) -> &Arena {
+        &self.config.arena
+    }
+
+    pub fn rules(self: &Self) -> &GameRules {
+        &self.config.rules
+    }
+
+    /// Side of the grid observation, the playable cells and the border walls around them
+    pub fn grid_side(self: &Self) -> usize {
+        self.config.rules.side as usize + 3
+    }
+
+    pub fn info(self: &Self) -> &StepInfo {
+        &self.info
+    }
+
+    pub fn reset(self: &mut Self, seed: u64) -> Observation {
+        *self = Self::start(self.config.clone(), seed);
+        self.observe()
+    }
+
+    /// Tops the board up to the apples of the rules, as the game does
+    fn spawn_apples(self: &mut Self) {
+        while self.apples.len() < self.config.rules.apples {
+            let Some(apple) = self.occupancy.random_free_cell(&self.rng) else {
+                return;
+            };
+            self.occupancy
+                .set(apple, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+            self.apples.push(apple);
+        }
+    }
+
+    /// Steps of a finished episode do nothing until the next reset
+    pub fn step(self: &mut Self, action: Action) -> (Observation, f32, bool, StepInfo) {
+        if self.done {
+            return (self.observe(), 0., true, self.info.clone());
+        }
+        let rewards = self.config.rewards;
+        let mut reward = rewards.step;
+        if let Some(direction) = action.direction() {
+            self.head.turn(direction);
+        }
+        self.info.steps += 1;
+        let rules = self.config.rules;
+        if let Some(end) = find_collision(&self.head, &self.body, &self.occupancy, &rules) {
+            self.info.end = Some(end.cause);
+            self.info.end_cell = end.cell;
+            self.done = true;
+            reward += rewards.death;
+            return (self.observe(), reward, true, self.info.clone());
+        }
+
+        let next = rules.next_cell(self.body.head().cell, self.head.direction().as_ivec2());
+        let eats = matches!(self.occupancy.get(next), Some(Occupant::Apple(_)));
+        self.occupancy.add_segment(next);
+        self.body.advance(next);
+        self.occupancy.remove_segment(self.body.vacated());
+        if eats {
+            self.apples.retain(|apple| *apple != next);
+            for _ in 0..rules.growth {
+                let segment = self.body.grow(Entity::PLACEHOLDER);
+                self.occupancy.add_segment(segment.cell);
+            }
+            self.info.apples += 1;
+            self.idle = 0;
+            reward += rewards.apple;
+            // Same win condition as check_game_over
+            if self.occupancy.is_filled() {
+                self.apples.clear();
+                self.info.end = Some(EndCause::BoardFilled);
+                self.done = true;
+                reward += rewards.victory;
+            } else {
+                self.spawn_apples();
+            }
+        } else {
+            self.idle += 1;
+            if let Some(max_idle_steps) = self.config.max_idle_steps
+                && self.idle >= max_idle_steps
+            {
+                self.info.truncated = true;
+                self.done = true;
+                reward += rewards.starved;
+            }
+        }
+        self.info.length = self.body.len();
+        (self.observe(), reward, self.done, self.info.clone())
+    }
+
+    pub fn observe(self: &Self) -> Observation {
+        match self.config.observation {
+            ObservationKind::Grid => self.grid(),
+            ObservationKind::Features => self.features(),
+        }
+    }
+
+    fn grid(self: &Self) -> Observation {
+        let grid_side = self.grid_side();
+        let rules = self.config.rules;
+        let mut data = vec![0.; GRID_CHANNELS * grid_side * grid_side];
+        // The grid has a ring of border cells around the board, only walls go there
+        let index = |channel: usize, cell: IVec2| {
+            let (x, y) = ((cell.x + 1) as usize, (cell.y + 1) as usize);
+            (channel * grid_side + y) * grid_side + x
+        };
+        let mut mark = |channel: usize, cell: IVec2| {
+            if rules.is_inside(cell) {
+                data[index(channel, cell)] = 1.;
+            }
+        };
+        mark(0, self.body.head().cell);
+        for segment in self.body.iter().skip(1) {
+            mark(1, segment.cell);
+        }
+        for apple in &self.apples {
+            mark(2, *apple);
+        }
+        for y in -1..=rules.side + 1 {
+            for x in -1..=rules.side + 1 {
+                let cell = IVec2::new(x, y);
+                if matches!(
+                    self.occupancy.get(cell),
+                    Some(Occupant::Wall(_) | Occupant::Obstacle(_))
+                ) {
+                    data[index(3, cell)] = 1.;
+                }
+            }
+        }
+        Observation {
+            data,
+            shape: vec![GRID_CHANNELS, grid_side, grid_side],
+        }
+    }
+
+    fn features(self: &Self) -> Observation {
+        let direction = self.head.direction();
+        let danger = |direction: Vec2| {
+            let head = SnakeHead::new(direction);
+            let collision = find_collision(&head, &self.body, &self.occupancy, &self.config.rules);
+            f32::from(collision.is_some())
+        };
+        let head = self.body.head().cell;
+        let apple = self
+            .apples
+            .iter()
+            .copied()
+            .min_by_key(|apple| (*apple - head).abs().element_sum())
+            .unwrap_or(head);
+        let flag = |value: bool| f32::from(value);
+        let data = vec![
+            danger(direction),
+            danger(direction.perp()),
+            danger(-direction.perp()),
+            flag(direction == Vec2::Y),
+            flag(direction == Vec2::NEG_Y),
+            flag(direction == Vec2::NEG_X),
+            flag(direction == Vec2::X),
+            flag(apple.y > head.y),
+            flag(apple.y < head.y),
+            flag(apple.x < head.x),
+            flag(apple.x > head.x),
+            head.x as f32 / self.config.rules.side as f32,
+            head.y as f32 / self.config.rules.side as f32,
+            self.body.len() as f32 / self.config.rules.board_cells() as f32,
+        ];
+        Observation {
+            data,
+            shape: vec![FEATURES],
+        }
+    }
+}
+
+/// A batch of environments stepped in parallel on the compute task pool. Finished episodes are
+/// reset right away: their step returns the final reward, done and info along with the first
+/// observation of the next episode.
+pub struct VecEnv {
+    envs: Vec<SnakeEnv>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl VecEnv {
+    pub fn new(count: usize, config: EnvConfig) -> Self {
+        Self {
+            envs: (0..count).map(|_| SnakeEnv::new(config.clone())).collect(),
+        }
+    }
+
+    pub fn len(self: &Self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Environment `i` gets the seed `seed + i`, later episodes keep counting from there
+    pub fn reset(self: &mut Self, seed: u64) -> Vec<Observation> {
+        self.envs
+            .iter_mut()
+            .enumerate()
+            .map(|(idx, env)| env.reset(seed + idx as u64))
+            .collect()
+    }
+
+    /// Takes one action per environment, in order
+    pub fn step(self: &mut Self, actions: &[Action]) -> Vec<(Observation, f32, bool, StepInfo)> {
+        assert_eq!(actions.len(), self.envs.len(), "One action per environment");
+        let count = self.envs.len() as u64;
+        let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        let chunk_size = self.envs.len().div_ceil(pool.thread_num()).max(1);
+        self.envs
+            .par_chunk_map_mut(pool, chunk_size, |chunk, envs| {
+                envs.iter_mut()
+                    .zip(&actions[chunk * chunk_size..])
+                    .map(|(env, action)| {
+                        let (mut observation, reward, done, info) = env.step(*action);
+                        if done {
+                            observation = env.reset(env.seed() + count);
+                        }
+                        (observation, reward, done, info)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOVES: [(Action, IVec2); 4] = [
+        (Action::Up, IVec2::Y),
+        (Action::Down, IVec2::NEG_Y),
+        (Action::Left, IVec2::NEG_X),
+        (Action::Right, IVec2::X),
+    ];
+
+    /// Heads for the nearest apple through cells that are safe right now
+    fn greedy(env: &SnakeEnv) -> Action {
+        let head = env.body.head().cell;
+        let target = env
+            .apples
+            .iter()
+            .min_by_key(|apple| (**apple - head).abs().element_sum())
+            .copied()
+            .unwrap_or(head);
+        MOVES
+            .into_iter()
+            .filter(|(_, direction)| *direction != -env.direction())
+            .map(|(action, direction)| (action, env.rules().next_cell(head, direction)))
+            .filter(|(_, next)| matches!(env.occupancy.get(*next), None | Some(Occupant::Apple(_))))
+            .min_by_key(|(_, next)| (target - *next).abs().element_sum())
+            .map_or(Action::Straight, |(action, _)| action)
+    }
+
+    /// Every empty playable cell is free and nothing else is, with stacked segments counted once
+    fn assert_board(env: &SnakeEnv) {
+        env.occupancy.assert_consistent();
+        let mut snake: Vec<IVec2> = env.snake().collect();
+        snake.sort_by_key(|cell| (cell.x, cell.y));
+        snake.dedup();
+        assert_eq!(
+            env.occupancy.free_cells().len() + snake.len() + env.apples.len(),
+            env.rules().board_cells()
+        );
+    }
+
+    #[test]
+    fn big_board_with_a_long_snake_stays_consistent() {
+        let rules = GameRules {
+            side: 199,
+            apples: 30,
+            growth: 40,
+            start_length: 150,
+            ..GameRules::DEFAULT
+        };
+        let mut env = SnakeEnv::new(EnvConfig {
+            rules,
+            arena: Arena::new(&rules),
+            observation: ObservationKind::Features,
+            max_idle_steps: None,
+            ..default()
+        });
+        env.reset(7);
+        assert_eq!(env.info().length, 150);
+        let mut longest = 0;
+        for step in 0..6000 {
+            let (_, _, done, info) = env.step(greedy(&env));
+            longest = longest.max(info.length);
+            if step % 200 == 0 || done {
+                assert_board(&env);
+            }
+            if done {
+                env.reset(step);
+            }
+        }
+        assert!(longest >= 1000, "the snake only got to {longest}");
+    }
+
+    fn config(observation: ObservationKind) -> EnvConfig {
+        EnvConfig {
+            rewards: Rewards {
+                step: -0.01,
+                apple: 1.,
+                death: -2.,
+                victory: 5.,
+                starved: -3.,
+            },
+            observation,
+            ..default()
+        }
+    }
+
+    /// Replaces the apples by a single one on `cell`
+    fn place_apple(env: &mut SnakeEnv, cell: IVec2) {
+        for apple in std::mem::take(&mut env.apples) {
+            env.occupancy.set(apple, None);
+        }
+        env.occupancy
+            .set(cell, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+        env.apples.push(cell);
+    }
+
+    fn clear_apples(env: &mut SnakeEnv) {
+        for apple in std::mem::take(&mut env.apples) {
+            env.occupancy.set(apple, None);
+        }
+    }
+
+    #[test]
+    fn same_seed_and_actions_play_out_the_same() {
+        let actions = [
+            Action::Straight,
+            Action::Left,
+            Action::Straight,
+            Action::Up,
+            Action::Right,
+            Action::Right,
+            Action::Down,
+            Action::Straight,
+        ];
+        let play = |seed: u64| {
+            let mut env = SnakeEnv::new(config(ObservationKind::Grid));
+            let mut trace = vec![(env.reset(seed).data, 0., false)];
+            for action in actions.iter().cycle().take(60) {
+                let (observation, reward, done, _) = env.step(*action);
+                trace.push((observation.data, reward, done));
+            }
+            (trace, env.snake().collect::<Vec<_>>())
+        };
+        assert_eq!(play(42), play(42));
+        assert_ne!(play(42), play(43));
+    }
+
+    #[test]
+    fn observations_have_their_documented_shape() {
+        let mut env = SnakeEnv::new(config(ObservationKind::Grid));
+        let grid = env.reset(1);
+        let side = env.grid_side();
+        assert_eq!(side, GameRules::DEFAULT.side as usize + 3);
+        assert_eq!(grid.shape, [GRID_CHANNELS, side, side]);
+        assert_eq!(grid.data.len(), GRID_CHANNELS * side * side);
+        // One head, one apple and the border
+        let channel = |idx: usize| {
+            grid.data[idx * side * side..(idx + 1) * side * side]
+                .iter()
+                .sum::<f32>()
+        };
+        assert_eq!(channel(0), 1.);
+        assert_eq!(channel(2), 1.);
+        assert_eq!(channel(3), (4 * side - 4) as f32);
+
+        let mut env = SnakeEnv::new(config(ObservationKind::Features));
+        let features = env.reset(1);
+        assert_eq!(features.shape, [FEATURES]);
+        assert_eq!(features.data.len(), FEATURES);
+        let (features, ..) = env.step(Action::Straight);
+        assert_eq!(features.data.len(), FEATURES);
+    }
+
+    #[test]
+    fn cells_off_the_board_are_left_out_of_the_grid() {
+        let mut env = SnakeEnv::new(config(ObservationKind::Grid));
+        let expected = env.reset(8).data;
+        // Nothing on the board ever gets there, the grid stays as it was instead of panicking
+        env.apples.push(IVec2::new(-1, 0));
+        env.apples.push(IVec2::splat(GameRules::DEFAULT.side + 5));
+        assert_eq!(env.observe().data, expected);
+    }
+
+    #[test]
+    fn eating_rewards_and_grows() {
+        let mut env = SnakeEnv::new(config(ObservationKind::Features));
+        env.reset(2);
+        let ahead = env.body.head().cell + env.direction();
+        place_apple(&mut env, ahead);
+        let (_, reward, done, info) = env.step(Action::Straight);
+        assert_eq!(reward, -0.01 + 1.);
+        assert!(!done);
+        assert_eq!(info.apples, 1);
+        assert_eq!(info.length, 2);
+        // Replaced right away
+        assert_eq!(env.apples().len(), 1);
+        assert_ne!(env.apples()[0], ahead);
+    }
+
+    #[test]
+    fn hitting_a_wall_is_a_death() {
+        let mut env = SnakeEnv::new(EnvConfig {
+            max_idle_steps: None,
+            ..config(ObservationKind::Features)
+        });
+        env.reset(3);
+        clear_apples(&mut env);
+        let mut last = None;
+        for _ in 0..=GameRules::DEFAULT.side {
+            let (_, reward, done, info) = env.step(Action::Straight);
+            if done {
+                last = Some((reward, info));
+                break;
+            }
+            assert_eq!(reward, -0.01);
+        }
+        let (reward, info) = last.expect("never reached the wall");
+        assert_eq!(reward, -0.01 - 2.);
+        assert_eq!(info.end, Some(EndCause::Wall));
+        assert!(!info.truncated);
+        // Finished episodes stay put
+        let (_, reward, done, _) = env.step(Action::Straight);
+        assert_eq!((reward, done), (0., true));
+    }
+
+    #[test]
+    fn starving_truncates_the_episode() {
+        let mut env = SnakeEnv::new(EnvConfig {
+            max_idle_steps: Some(3),
+            ..config(ObservationKind::Features)
+        });
+        env.reset(4);
+        clear_apples(&mut env);
+        // The spawn leaves a runway longer than that ahead
+        for _ in 0..2 {
+            let (_, _, done, _) = env.step(Action::Straight);
+            assert!(!done);
+        }
+        let (_, reward, done, info) = env.step(Action::Straight);
+        assert!(done && info.truncated);
+        assert_eq!(info.end, None);
+        assert_eq!(reward, -0.01 - 3.);
+    }
+
+    #[test]
+    fn filling_the_board_is_a_victory() {
+        let rules = GameRules {
+            side: 1,
+            ..GameRules::DEFAULT
+        };
+        let mut env = SnakeEnv::new(EnvConfig {
+            rules,
+            arena: Arena::new(&rules),
+            ..config(ObservationKind::Features)
+        });
+        env.reset(5);
+        let mut last = None;
+        for _ in 0..50 {
+            let (_, reward, done, info) = env.step(greedy(&env));
+            if done {
+                last = Some((reward, info));
+                break;
+            }
+        }
+        let (reward, info) = last.expect("the board was never filled");
+        assert_eq!(info.end, Some(EndCause::BoardFilled));
+        assert_eq!(info.length, 4);
+        assert_eq!(reward, -0.01 + 1. + 5.);
+        assert!(env.apples().is_empty());
+    }
+
+    #[test]
+    fn a_stacked_body_longer_than_the_board_doesnt_fill_it() {
+        let rules = GameRules {
+            side: 3,
+            growth: 20,
+            ..GameRules::DEFAULT
+        };
+        let mut env = SnakeEnv::new(EnvConfig {
+            rules,
+            arena: Arena::new(&rules),
+            ..config(ObservationKind::Features)
+        });
+        env.reset(6);
+        let ahead = env.body.head().cell + env.direction();
+        place_apple(&mut env, ahead);
+        let (_, _, done, info) = env.step(Action::Straight);
+        assert!(info.length >= rules.board_cells());
+        assert!(!done);
+        assert_eq!(info.end, None);
+        assert!(!env.occupancy.free_cells().is_empty());
+        assert_board(&env);
+    }
+
+    #[test]
+    fn vec_env_keeps_the_order_and_resets_with_the_next_seeds() {
+        const COUNT: usize = 13;
+        let config = EnvConfig {
+            // Every episode ends on its first step that doesn't eat
+            max_idle_steps: Some(1),
+            ..config(ObservationKind::Features)
+        };
+        let mut envs = VecEnv::new(COUNT, config.clone());
+        let mut alone: Vec<SnakeEnv> = (0..COUNT)
+            .map(|idx| {
+                let mut env = SnakeEnv::new(config.clone());
+                env.reset(100 + idx as u64);
+                env
+            })
+            .collect();
+        let first = envs.reset(100);
+        for (env, observation) in alone.iter().zip(&first) {
+            assert_eq!(env.observe().data, observation.data);
+        }
+        for round in 0..3 {
+            let actions: Vec<Action> = (0..COUNT)
+                .map(|idx| MOVES[(idx + round) % MOVES.len()].0)
+                .collect();
+            let results = envs.step(&actions);
+            assert_eq!(results.len(), COUNT);
+            for (idx, ((env, action), (observation, reward, done, info))) in
+                alone.iter_mut().zip(&actions).zip(results).enumerate()
+            {
+                let (mut expected, expected_reward, expected_done, expected_info) =
+                    env.step(*action);
+                if expected_done {
+                    expected = env.reset(env.seed() + COUNT as u64);
+                }
+                assert_eq!(observation.data, expected.data, "env {idx}");
+                assert_eq!(
+                    (reward, done, info.steps),
+                    (expected_reward, expected_done, expected_info.steps)
+                );
+                assert_eq!(envs.envs[idx].seed(), env.seed());
+            }
+        }
+        // Every episode ended at least once
+        assert!((0..COUNT).all(|idx| envs.envs[idx].seed() >= (100 + COUNT + idx) as u64));
+    }
+}
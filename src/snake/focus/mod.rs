@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Focus>()
+            .add_systems(
+                PreUpdate,
+                (release_buttons, navigate, follow_hover)
+                    .chain()
+                    .after(bevy::ui::UiSystem::Focus),
+            )
+            .add_systems(Update, (highlight_buttons, animate_press));
+    }
+}
+
+pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const FOCUSED_BUTTON: Color = Color::srgb(0.3, 0.3, 0.3);
+pub const PRESSED_BUTTON: Color = Color::srgb(0.45, 0.45, 0.45);
+pub const NORMAL_BORDER: Color = Color::BLACK;
+pub const FOCUSED_BORDER: Color = Color::WHITE;
+
+const PRESS_ANIMATION: Duration = Duration::from_millis(150);
+const PRESSED_SCALE: f32 = 0.9;
+
+/// Buttons that can be reached with the keyboard or a gamepad. The navigation order follows the
+/// layout, from top to bottom and then left to right.
+#[derive(Component)]
+pub struct MenuButton;
+
+/// The button activated by Escape, or the east gamepad button, on its screen.
+#[derive(Component)]
+pub struct BackButton;
+
+/// The button currently focused, hovering a button with the mouse also focuses it.
+#[derive(Resource, Default)]
+pub struct Focus(pub Option<Entity>);
+
+/// Buttons pressed from the keyboard/gamepad. The pointer never releases them, so they are
+/// released on the next frame.
+#[derive(Component)]
+struct KeyPressed;
+
+#[derive(Component)]
+struct PressAnimation(Timer);
+
+fn release_buttons(
+    mut commands: Commands,
+    pressed: Query<(Entity, &mut Interaction), With<KeyPressed>>,
+) {
+    for (entity, mut interaction) in pressed {
+        interaction.set_if_neq(Interaction::None);
+        commands.entity(entity).remove::<KeyPressed>();
+    }
+}
+
+fn navigate(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut focus: ResMut<Focus>,
+    mut buttons: Query<
+        (Entity, &GlobalTransform, &mut Interaction, Has<BackButton>),
+        With<MenuButton>,
+    >,
+) {
+    let gamepad_pressed =
+        |button: GamepadButton| gamepads.iter().any(|gamepad| gamepad.just_pressed(button));
+    let up = keys.just_pressed(KeyCode::ArrowUp) || gamepad_pressed(GamepadButton::DPadUp);
+    let down = keys.just_pressed(KeyCode::ArrowDown) || gamepad_pressed(GamepadButton::DPadDown);
+    let activate = keys.just_pressed(KeyCode::Enter)
+        || keys.just_pressed(KeyCode::Space)
+        || gamepad_pressed(GamepadButton::South);
+    let back = keys.just_pressed(KeyCode::Escape) || gamepad_pressed(GamepadButton::East);
+
+    let mut ordered: Vec<_> = buttons
+        .iter()
+        .map(|(entity, transform, _, is_back)| (entity, transform.translation(), is_back))
+        .collect();
+    ordered.sort_by(|a, b| a.1.y.total_cmp(&b.1.y).then(a.1.x.total_cmp(&b.1.x)));
+
+    let current = focus
+        .0
+        .and_then(|focused| ordered.iter().position(|(e, _, _)| *e == focused));
+    if current.is_none() {
+        focus.0 = None;
+    }
+    if ordered.is_empty() {
+        return;
+    }
+    if up || down {
+        let next = match (current, up) {
+            (None, true) => ordered.len() - 1,
+            (None, false) => 0,
+            (Some(idx), true) => (idx + ordered.len() - 1) % ordered.len(),
+            (Some(idx), false) => (idx + 1) % ordered.len(),
+        };
+        focus.0 = Some(ordered[next].0);
+    }
+
+    let target = if back {
+        ordered.iter().find(|(_, _, is_back)| *is_back).map(|b| b.0)
+    } else if activate {
+        focus.0
+    } else {
+        None
+    };
+    if let Some(target) = target
+        && let Ok((_, _, mut interaction, _)) = buttons.get_mut(target)
+    {
+        *interaction = Interaction::Pressed;
+        commands.entity(target).insert(KeyPressed);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn follow_hover(
+    mut focus: ResMut<Focus>,
+    buttons: Query<(Entity, &Interaction), (Changed<Interaction>, With<MenuButton>)>,
+) {
+    for (entity, interaction) in buttons {
+        if *interaction == Interaction::Hovered {
+            focus.0 = Some(entity);
+        }
+    }
+}
+
+fn highlight_buttons(
+    mut commands: Commands,
+    focus: Res<Focus>,
+    buttons: Query<
+        (
+            Entity,
+            Ref<Interaction>,
+            &mut BackgroundColor,
+            &mut BorderColor,
+        ),
+        With<MenuButton>,
+    >,
+) {
+    for (entity, interaction, mut background, mut border) in buttons {
+        let focused = focus.0 == Some(entity);
+        let (background_color, border_color) = match (*interaction, focused) {
+            (Interaction::Pressed, _) => (PRESSED_BUTTON, FOCUSED_BORDER),
+            (Interaction::Hovered, _) | (Interaction::None, true) => {
+                (FOCUSED_BUTTON, FOCUSED_BORDER)
+            }
+            (Interaction::None, false) => (NORMAL_BUTTON, NORMAL_BORDER),
+        };
+        background.set_if_neq(BackgroundColor(background_color));
+        border.set_if_neq(BorderColor(border_color));
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            commands
+                .entity(entity)
+                .insert(PressAnimation(Timer::new(PRESS_ANIMATION, TimerMode::Once)));
+        }
+    }
+}
+
+/// Shrinks the pressed button and lets it grow back
+fn animate_press(
+    mut commands: Commands,
+    time: Res<Time>,
+    buttons: Query<(Entity, &mut PressAnimation, &mut Transform)>,
+) {
+    for (entity, mut animation, mut transform) in buttons {
+        animation.0.tick(time.delta());
+        let t = animation.0.fraction();
+        transform.scale = Vec3::splat(PRESSED_SCALE + (1. - PRESSED_SCALE) * t);
+        if animation.0.finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<PressAnimation>();
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen};
+use super::rules::RulesPresets;
+
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .add_menu(MenuId::Modes, modes_menu);
+    }
+}
+
+/// Rules of the run being played, picked before starting it.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    /// Eat as many apples as possible before the clock runs out. Walls cost time instead of
+    /// ending the run.
+    TimeAttack,
+    /// Handcrafted levels with their own goal, see the campaign module.
+    Campaign,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl GameMode {
+    pub fn respawns_on_wall(self: &Self) -> bool {
+        matches!(self, GameMode::TimeAttack)
+    }
+}
+
+/// Starts a run on an empty arena, with the rules picked in the settings. The campaign starts its
+/// levels on its own.
+pub fn start(world: &mut World, mode: GameMode) {
+    let rules = world
+        .resource::<RulesPresets>()
+        .get(&world.resource::<super::Settings>().rules);
+    world.insert_resource(super::gameplay::Arena::new(&rules));
+    world.insert_resource(rules);
+    world.insert_resource(mode);
+    world
+        .resource_mut::<NextState<super::GameState>>()
+        .set(super::GameState::Gameplay);
+}
+
+fn modes_menu(_world: &World) -> MenuScreen {
+    MenuScreen {
+        title: Some("modes.title"),
+        items: vec![
+            MenuItem::Button {
+                label: "modes.classic",
+                action: MenuAction::Run(|world| start(world, GameMode::Classic)),
+            },
+            MenuItem::Button {
+                label: "modes.time_attack",
+                action: MenuAction::Run(|world| start(world, GameMode::TimeAttack)),
+            },
+            MenuItem::Button {
+                label: "modes.campaign",
+                action: MenuAction::Push(MenuId::Campaign),
+            },
+            MenuItem::Button {
+                label: "modes.versus",
+                action: MenuAction::Push(MenuId::Versus),
+            },
+            MenuItem::Button {
+                label: "common.back",
+                action: MenuAction::Back,
+            },
+        ],
+    }
+}
@@ -1,7 +1,105 @@
-use bevy::{math::FloatPow, prelude::*};
+use bevy::prelude::*;
+
+use super::GameMode;
+use super::campaign;
+use super::gameplay::{EndCause, LastGameEnd};
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen, MenuStack};
 
 pub struct GameOverPlugin;
 
 impl Plugin for GameOverPlugin {
-    fn build(&self, app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.add_menu(MenuId::GameOver, gameover_menu)
+            .add_systems(
+                OnEnter(super::GameState::Gameover),
+                (open_menu, spawn_camera),
+            )
+            .add_systems(
+                OnExit(super::GameState::Gameover),
+                (despawn_camera, close_menu),
+            );
+    }
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+pub fn retry(world: &mut World) {
+    match *world.resource::<GameMode>() {
+        GameMode::Campaign => campaign::start_level(world, campaign::current_level(world)),
+        mode => super::game_mode::start(world, mode),
+    }
+}
+
+fn gameover_menu(world: &World) -> MenuScreen {
+    let campaign = *world.resource::<GameMode>() == GameMode::Campaign;
+    let cleared = campaign && campaign::level_cleared(world);
+    let next_level = campaign.then(|| campaign::next_level(world)).flatten();
+    let title = match (cleared, next_level) {
+        (false, _) => "gameover.title",
+        (true, Some(_)) => "gameover.level_complete",
+        (true, None) => "gameover.campaign_complete",
+    };
+
+    let mut items = Vec::new();
+    if let Some(reason) = end_reason(world) {
+        items.push(MenuItem::Label(reason));
+    }
+    if next_level.is_some() {
+        items.push(MenuItem::Button {
+            label: "gameover.next_level",
+            action: MenuAction::Run(|world| {
+                if let Some(next) = campaign::next_level(world) {
+                    campaign::start_level(world, next);
+                }
+            }),
+        });
+    }
+    items.push(MenuItem::Button {
+        label: "gameover.retry",
+        action: MenuAction::Run(retry),
+    });
+    items.push(MenuItem::Button {
+        label: "gameover.main_menu",
+        action: MenuAction::Run(|world| {
+            world
+                .resource_mut::<NextState<super::GameState>>()
+                .set(super::GameState::Main)
+        }),
+    });
+    MenuScreen {
+        title: Some(title),
+        items,
+    }
+}
+
+/// Tells the player why the run ended, beaten levels need no explanation
+fn end_reason(world: &World) -> Option<String> {
+    let end = world.resource::<LastGameEnd>().0?;
+    let key = match end.cause {
+        EndCause::Wall => "gameover.cause.wall",
+        EndCause::SelfBite => "gameover.cause.self_bite",
+        EndCause::Obstacle => "gameover.cause.obstacle",
+        EndCause::BoardFilled => "gameover.cause.board_filled",
+        EndCause::Timeout => "gameover.cause.timeout",
+        EndCause::GoalReached => return None,
+    };
+    let (x, y) = end.cell.map_or((String::new(), String::new()), |cell| {
+        (cell.x.to_string(), cell.y.to_string())
+    });
+    Some(world.resource::<Localization>().format(key, &[&x, &y]))
+}
+
+fn open_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.open(MenuId::GameOver);
+}
+
+fn close_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.clear();
+}
+
+fn despawn_camera(mut commands: Commands, camera: Single<Entity, With<Camera2d>>) {
+    commands.entity(*camera).despawn();
 }
@@ -1,4 +1,9 @@
-use bevy::{platform::collections::HashSet, prelude::*};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::body::SnakeBody;
+use super::occupancy::{Occupancy, Occupant};
+use super::rules::GameRules;
 
 pub struct GameplayPlugin;
 
@@ -6,32 +11,67 @@ impl Plugin for GameplayPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<super::SnakeResourceManager>()
             .init_resource::<super::RngResource>()
-            .init_resource::<super::AtomicCounter>()
-            .init_resource::<SnakeLast>()
+            .init_resource::<GameRules>()
+            .init_resource::<Arena>()
+            .insert_resource(Occupancy::new(GameRules::DEFAULT.side))
             .add_event::<AppleEaten>()
+            .add_event::<SnakeMoved>()
+            .add_event::<WallHit>()
+            .add_event::<SpeedChanged>()
+            .add_event::<GameEnd>()
+            .init_resource::<LastGameEnd>()
             .add_systems(
                 OnEnter(super::GameState::Gameplay),
-                (spawn_map, spawn_head, initialize_fixed_step),
+                (
+                    (reset_board, spawn_map, spawn_head)
+                        .chain()
+                        .in_set(SpawnBoard),
+                    initialize_fixed_step,
+                    reset_last_game_end,
+                ),
             )
             .add_systems(
                 FixedUpdate,
                 move_player
+                    .in_set(SnakeStep)
                     .after(process_input)
                     .before(check_eaten_apple)
                     .run_if(in_state(super::GameState::Gameplay))
                     .run_if(not(collision))
-                    .run_if(not(in_state(super::GameplayState::Paused))),
+                    .run_if(super::remote::may_step)
+                    .run_if(in_state(super::GameplayState::Running)),
             )
             .add_systems(
                 Update,
                 (
-                    process_input,
                     check_eaten_apple.before(despawn_apple),
                     increase_fixed_update.after(check_eaten_apple),
                     spawn_snake_part.after(move_player),
                     despawn_apple,
-                    spawn_apple.after(spawn_snake_part),
-                    check_game_over.after(move_player).after(spawn_snake_part),
+                    check_game_over
+                        .in_set(EndRun)
+                        .after(move_player)
+                        .after(spawn_snake_part),
+                    respawn_snake.in_set(EndRun).after(check_game_over),
+                )
+                    .run_if(in_state(super::GameState::Gameplay))
+                    .run_if(in_state(super::GameplayState::Running)),
+            )
+            // Also during the countdown, a run can end before it starts when the snake has no
+            // room to spawn
+            .add_systems(
+                Update,
+                end_run
+                    .after(EndRun)
+                    .run_if(in_state(super::GameState::Gameplay)),
+            )
+            // Also during the countdown, so the player sees the apple and can pick a direction
+            .add_systems(
+                Update,
+                (
+                    process_input,
+                    paint_snake,
+                    spawn_apple.after(spawn_snake_part).after(despawn_apple),
                 )
                     .run_if(in_state(super::GameState::Gameplay))
                     .run_if(not(in_state(super::GameplayState::Paused))),
@@ -40,84 +80,223 @@ impl Plugin for GameplayPlugin {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy)]
 pub struct SnakeHead(Vec2);
 
+#[allow(clippy::needless_arbitrary_self_type)]
+impl SnakeHead {
+    pub fn new(direction: Vec2) -> Self {
+        Self(direction)
+    }
+
+    pub fn direction(self: &Self) -> Vec2 {
+        self.0
+    }
+
+    /// Turning back onto the body is ignored
+    pub fn turn(self: &mut Self, direction: Vec2) {
+        if direction.dot(self.0) != -1. {
+            self.0 = direction;
+        }
+    }
+}
+
+/// The fixed step moving the snake, for systems that have to run around it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SnakeStep;
+
+/// Every system that can write GameEnd, the run is wrapped up after all of them so none is missed.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EndRun;
+
+/// Lays out the walls and the snake when a run starts.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpawnBoard;
+
+#[derive(Component)]
+pub struct SnakePart;
+
+#[derive(Component)]
+pub struct Wall;
+
+/// Walls placed inside the board by the arena, on top of the border ones.
 #[derive(Component)]
-pub struct SnakePart(usize);
+pub struct Obstacle;
+
+/// Free cells a snake gets ahead of it when it starts or respawns, whenever the board has room
+const SPAWN_RUNWAY: i32 = 5;
+const DIRECTIONS: [IVec2; 4] = [IVec2::NEG_X, IVec2::X, IVec2::NEG_Y, IVec2::Y];
+
+/// Board layout and pace of the next run, set by whoever starts it along with the GameRules.
+/// Obstacles are distinct cells inside the board, the border walls are always there.
+#[derive(Resource, Clone, Debug)]
+pub struct Arena {
+    pub obstacles: Vec<IVec2>,
+    pub start_hz: f64,
+    /// Segments of the snake when the run starts, laid out behind the head
+    pub start_length: usize,
+}
+
+impl Arena {
+    /// An empty board with the pace and length of the rules
+    pub fn new(rules: &GameRules) -> Self {
+        Self {
+            obstacles: Vec::new(),
+            start_hz: rules.speed.start_hz,
+            start_length: rules.start_length,
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new(&GameRules::DEFAULT)
+    }
+}
+
+fn reset_board(mut occupancy: ResMut<Occupancy>, rules: Res<GameRules>) {
+    *occupancy = Occupancy::new(rules.side);
+}
 
-pub const PLAY_SIDE: f32 = 30.;
-const HALF_PLAY_SIDE: f32 = PLAY_SIDE / 2.;
-const INITIAL_Z: f32 = -50.;
-const MIN_HZ: f64 = 10.;
-const MAX_HZ: f64 = 30.;
+fn initialize_fixed_step(
+    mut fixed_time: ResMut<Time<Fixed>>,
+    settings: Res<super::Settings>,
+    arena: Res<Arena>,
+) {
+    fixed_time.set_timestep_hz(arena.start_hz * settings.difficulty.speed_multiplier());
+}
 
-fn initialize_fixed_step(mut fixed_time: ResMut<Time<Fixed>>) {
-    fixed_time.set_timestep_hz(MIN_HZ);
+/// Where a snake of `length` segments starts and where it heads, picked at random among the
+/// cells with room for the body straight behind the head, then with the longest free runway
+/// ahead (up to SPAWN_RUNWAY). When the body fits nowhere it is stacked on the head and unfolds
+/// as it moves.
+/// Returns the cells of the body from the head, and the direction.
+pub fn find_spawn(
+    occupancy: &Occupancy,
+    rng: &super::RngResource,
+    length: usize,
+) -> Option<(Vec<IVec2>, IVec2)> {
+    let is_free = |cell: IVec2| occupancy.get(cell).is_none();
+    let mut best = (false, 0);
+    let mut candidates = Vec::new();
+    for head in occupancy.free_cells() {
+        for direction in DIRECTIONS {
+            let runway = (1..=SPAWN_RUNWAY)
+                .take_while(|step| is_free(head + direction * step))
+                .count();
+            let fits = (1..length as i32).all(|step| is_free(head - direction * step));
+            let key = (fits, runway);
+            if key > best {
+                best = key;
+                candidates.clear();
+            }
+            if key == best {
+                candidates.push((*head, direction));
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+    let (head, direction) = candidates[rng.random_in_range(0..candidates.len() as u64) as usize];
+    let body = (0..length.max(1) as i32)
+        .map(|step| {
+            if best.0 {
+                head - direction * step
+            } else {
+                head
+            }
+        })
+        .collect();
+    Some((body, direction))
 }
 
 fn spawn_head(
     mut commands: Commands,
     snake_resources: Res<super::SnakeResourceManager>,
     rng: Res<super::RngResource>,
-    id: Res<super::AtomicCounter>,
+    arena: Res<Arena>,
+    rules: Res<GameRules>,
+    mut occupancy: ResMut<Occupancy>,
+    mut game_end_event: EventWriter<GameEnd>,
 ) {
-    let x_snake_head = rng.random_in_range(0..PLAY_SIDE as u64) as f32 - HALF_PLAY_SIDE;
-    let y_snake_head = rng.random_in_range(0..PLAY_SIDE as u64) as f32 - HALF_PLAY_SIDE;
-    let (x, y) = match rng.random_in_range(0..4) {
-        0 => (-1, 0),
-        1 => (1, 0),
-        2 => (0, -1),
-        3 => (0, 1),
-        _ => panic!("Range should be between 0 and 4"),
+    let Some((cells, direction)) = find_spawn(&occupancy, &rng, arena.start_length) else {
+        warn!("The obstacles leave no room for the snake, ending the run");
+        game_end_event.write(GameEnd::new(EndCause::Obstacle));
+        return;
     };
-    // Spawns the snake head
-    commands.spawn((
-        SnakeHead(Vec2::new(x as f32, y as f32)),
-        SnakePart(id.get_id()),
-        Transform::from_translation(Vec3::new(x_snake_head, y_snake_head, INITIAL_Z)),
-        Mesh3d(snake_resources.ball_mesh()),
-        MeshMaterial3d(snake_resources.ball_material(
-            rng.random_in_range(0..(snake_resources.ball_materials_count() as u64)) as usize,
-        )),
-    ));
-}
-
-fn is_inside(x: i32, y: i32, side: i32) -> bool {
-    if (x < 0 || x > side) || (y < 0 || y > side) {
-        return false;
+    let length = cells.len();
+    let head = commands
+        .spawn((
+            SnakeHead(direction.as_vec2()),
+            SnakePart,
+            Transform::from_translation(rules.cell_position(cells[0])),
+            Mesh3d(snake_resources.head_mesh()),
+            MeshMaterial3d(snake_resources.segment_material(0, length)),
+        ))
+        .id();
+    let mut body = SnakeBody::new(head, cells[0]);
+    occupancy.add_segment(cells[0]);
+    for (idx, cell) in cells.iter().enumerate().skip(1) {
+        let part = commands
+            .spawn((
+                SnakePart,
+                Transform::from_translation(rules.cell_position(*cell)),
+                Mesh3d(snake_resources.ball_mesh()),
+                MeshMaterial3d(snake_resources.segment_material(idx, length)),
+            ))
+            .id();
+        body.push(part, *cell);
+        occupancy.add_segment(*cell);
     }
-    true
+    commands.entity(head).insert(body);
 }
 
 fn spawn_map(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    snake_resources: Res<super::SnakeResourceManager>,
+    arena: Res<Arena>,
+    rules: Res<GameRules>,
+    mut occupancy: ResMut<Occupancy>,
 ) {
-    commands.spawn(DirectionalLight::default());
-    commands.spawn(Camera3d::default());
-    let cube = meshes.add(Cuboid::from_length(1.));
-    let material = materials.add(StandardMaterial {
-        base_color: Color::hsl(360., 1., 0.),
-        ..Default::default()
+    let lighting = &snake_resources.theme().lighting;
+    commands.spawn(DirectionalLight {
+        color: lighting.color.into(),
+        illuminance: lighting.illuminance,
+        ..default()
     });
+    commands.spawn(Camera3d::default());
+    let cube = snake_resources.wall_mesh();
+    let material = snake_resources.wall_material();
 
-    for y in -1..=PLAY_SIDE as i32 + 1 {
-        for x in -1..=PLAY_SIDE as i32 + 1 {
-            if !is_inside(x, y, PLAY_SIDE as i32) {
-                commands.spawn((
-                    Transform::from_translation(Vec3::new(
-                        x as f32 - HALF_PLAY_SIDE,
-                        y as f32 - HALF_PLAY_SIDE,
-                        INITIAL_Z,
-                    )),
-                    Mesh3d(cube.clone()),
-                    MeshMaterial3d(material.clone()),
-                ));
+    for y in -1..=rules.side + 1 {
+        for x in -1..=rules.side + 1 {
+            let cell = IVec2::new(x, y);
+            if !rules.is_inside(cell) {
+                let wall = commands
+                    .spawn((
+                        Wall,
+                        Transform::from_translation(rules.cell_position(cell)),
+                        Mesh3d(cube.clone()),
+                        MeshMaterial3d(material.clone()),
+                    ))
+                    .id();
+                occupancy.set(cell, Some(Occupant::Wall(wall)));
             }
         }
     }
+    for obstacle in &arena.obstacles {
+        let entity = commands
+            .spawn((
+                Wall,
+                Obstacle,
+                Transform::from_translation(rules.cell_position(*obstacle)),
+                Mesh3d(cube.clone()),
+                MeshMaterial3d(material.clone()),
+            ))
+            .id();
+        occupancy.set(*obstacle, Some(Occupant::Obstacle(entity)));
+    }
 }
 
 fn process_input(mut snake_head: Single<&mut SnakeHead>, input: Res<ButtonInput<KeyCode>>) {
@@ -140,99 +319,196 @@ fn process_input(mut snake_head: Single<&mut SnakeHead>, input: Res<ButtonInput<
             direction_delta.x = 1.;
             direction_delta.y = 0.;
         }
-        let dot = direction_delta.dot(snake_head.0);
-        if dot != -1. {
-            snake_head.0 = direction_delta;
-        }
+        snake_head.turn(direction_delta);
     }
 }
 
-pub fn collision(
-    head: Single<&SnakeHead>,
-    parts: Query<(&Transform, &SnakePart)>,
-    collidable_others: Query<
-        &Transform,
-        (
-            Without<Apple>,
-            Without<SnakePart>,
-            Without<DirectionalLight>,
-            Without<Camera3d>,
-        ),
-    >,
+/// What the head would run into on the next move, if anything. Only the death causes come out
+/// of here, and the entity of a bitten segment is left for the caller to find.
+pub fn find_collision(
+    head: &SnakeHead,
+    body: &SnakeBody,
+    occupancy: &Occupancy,
+    rules: &GameRules,
+) -> Option<GameEnd> {
+    let next = rules.next_cell(body.head().cell, head.0.as_ivec2());
+    let (cause, entity) = match occupancy.get(next)? {
+        Occupant::Wall(entity) => (EndCause::Wall, Some(entity)),
+        Occupant::Obstacle(entity) => (EndCause::Obstacle, Some(entity)),
+        // The tail leaves its cell on this same move, unless the body is stacked there
+        Occupant::Snake(1) if body.tail().cell == next => return None,
+        Occupant::Snake(_) if !rules.self_collision => return None,
+        Occupant::Snake(_) => (EndCause::SelfBite, None),
+        Occupant::Apple(_) => return None,
+    };
+    Some(GameEnd {
+        cause,
+        cell: Some(next),
+        entity,
+    })
+}
+
+fn collision(
+    snake: Single<(&SnakeHead, &SnakeBody)>,
+    occupancy: Res<Occupancy>,
+    rules: Res<GameRules>,
 ) -> bool {
-    let mut v = Vec::new();
-    for (t, s) in parts {
-        v.push((s.0, t));
-    }
-    v.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-    let mut v: Vec<_> = v
-        .into_iter()
-        .map(|(_, t)| (*t).translation.truncate())
-        .collect();
-    for i in (0..v.len()).rev() {
-        if i != 0 {
-            v[i] = v[i - 1];
-        } else {
-            v[i] += head.0;
-        }
-    }
-    for l in v[1..].iter() {
-        if *l == v[0] {
-            return true;
-        }
-    }
-    for collidable in collidable_others {
-        if collidable.translation.truncate() == v[0] {
-            return true;
-        }
-    }
-    false
+    find_collision(snake.0, snake.1, &occupancy, &rules).is_some()
 }
 
+/// Only the head and the former tail move, see SnakeBody
 fn move_player(
-    head: Single<&SnakeHead>,
-    parts: Query<(&mut Transform, &SnakePart)>,
-    mut last_part: ResMut<SnakeLast>,
+    snake: Single<(&SnakeHead, &mut SnakeBody)>,
+    mut parts: Query<(&mut Transform, &mut MeshMaterial3d<StandardMaterial>), With<SnakePart>>,
+    mut occupancy: ResMut<Occupancy>,
+    snake_resources: Res<super::SnakeResourceManager>,
+    rules: Res<GameRules>,
+    mut snake_moved_event: EventWriter<SnakeMoved>,
 ) {
-    // Small N for snake_parts, thus it is not an issue to sort it every frame
-    let mut v = Vec::new();
-    v.reserve_exact(parts.iter().count());
-    for (t, s) in parts {
-        v.push((s.0, t));
-    }
-    v.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-    let mut last = Vec3::ZERO;
-    for (idx, (_, mut part_transform)) in v.into_iter().enumerate() {
-        let current = part_transform.translation;
-        if idx == 0 {
-            part_transform.translation.x += head.0.x;
-            part_transform.translation.y += head.0.y;
-        } else {
-            part_transform.translation = last;
+    let (head, mut body) = snake.into_inner();
+    let next = rules.next_cell(body.head().cell, head.0.as_ivec2());
+    occupancy.add_segment(next);
+    let moved = body.advance(next);
+    occupancy.remove_segment(body.vacated());
+    snake_moved_event.write(SnakeMoved);
+    if let Ok((mut transform, _)) = parts.get_mut(body.head().entity) {
+        transform.translation = rules.cell_position(next);
+    }
+    let Some(moved) = moved else {
+        return;
+    };
+    if let Ok((mut transform, mut material)) = parts.get_mut(moved.entity) {
+        transform.translation = rules.cell_position(moved.cell);
+        material.0 = segment_material(&body, 1, &snake_resources);
+    }
+    // Gradients follow the index along the whole body, which every move shifts. Growing repaints
+    // the whole body in paint_snake.
+    for idx in snake_resources.color_changes(body.len()) {
+        if let Some(segment) = body.get(idx)
+            && let Ok((_, mut material)) = parts.get_mut(segment.entity)
+        {
+            material.0 = segment_material(&body, idx, &snake_resources);
         }
-        last = current;
     }
-    last_part.0.x = last.x;
-    last_part.0.y = last.y;
+}
+
+fn segment_material(
+    body: &SnakeBody,
+    idx: usize,
+    snake_resources: &super::SnakeResourceManager,
+) -> Handle<StandardMaterial> {
+    let skin = if snake_resources.is_periodic() {
+        body.skin(idx)
+    } else {
+        idx
+    };
+    snake_resources.segment_material(skin, body.len())
 }
 
 #[derive(Component)]
 pub struct Apple;
 
+/// The snake moved a cell, once per fixed step that moved it.
 #[derive(Event)]
-struct AppleEaten;
+pub struct SnakeMoved;
 
+/// One per apple, each one is despawned and replaced on its own
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AppleEaten {
+    pub entity: Entity,
+    pub cell: IVec2,
+}
+
+/// The pace changed, `at_max` tells whether it can't go any faster.
+#[derive(Event)]
+pub struct SpeedChanged {
+    pub hz: f64,
+    pub at_max: bool,
+}
+
+impl SpeedChanged {
+    /// Pace once a snake of `parts` segments eats an apple, following the speed curve of the
+    /// rules from the pace of the arena
+    pub fn after_apple(
+        arena: &Arena,
+        rules: &GameRules,
+        parts: usize,
+        speed_multiplier: f64,
+    ) -> Self {
+        let log_parts = (parts as f64).log2();
+        let max_hz = rules.speed.max_hz.max(arena.start_hz);
+        let mut hz = (arena.start_hz + log_parts * rules.speed.per_doubling).next_up();
+        hz = hz.clamp(arena.start_hz, max_hz);
+        let hz = hz * speed_multiplier;
+        Self {
+            hz,
+            at_max: hz >= max_hz * speed_multiplier,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndCause {
+    Wall,
+    SelfBite,
+    Obstacle,
+    BoardFilled,
+    /// The clock of a timed mode or level ran out
+    Timeout,
+    /// A campaign level was beaten
+    GoalReached,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl EndCause {
+    pub fn is_death(self: &Self) -> bool {
+        matches!(
+            self,
+            EndCause::Wall | EndCause::SelfBite | EndCause::Obstacle | EndCause::Timeout
+        )
+    }
+}
+
+/// The run is over, sent by whatever ended it. Collisions tell the cell the head moved into and
+/// what was there.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameEnd {
+    pub cause: EndCause,
+    pub cell: Option<IVec2>,
+    pub entity: Option<Entity>,
+}
+
+impl GameEnd {
+    pub fn new(cause: EndCause) -> Self {
+        Self {
+            cause,
+            cell: None,
+            entity: None,
+        }
+    }
+}
+
+/// How the last run ended, kept around for the game over screen.
 #[derive(Resource, Default)]
-struct SnakeLast(Vec2);
+pub struct LastGameEnd(pub Option<GameEnd>);
 
+/// The head ran into a wall in a mode where that doesn't end the run.
+#[derive(Event)]
+pub struct WallHit;
+
+#[allow(clippy::type_complexity)]
 fn check_eaten_apple(
     head: Single<&Transform, With<SnakeHead>>,
-    apple: Option<Single<&Transform, (With<Apple>, Without<SnakeHead>)>>,
+    apples: Query<(Entity, &Transform), (With<Apple>, Without<SnakeHead>)>,
+    rules: Res<GameRules>,
     mut apple_eaten_event: EventWriter<AppleEaten>,
 ) {
-    if let Some(apple) = apple {
-        if apple.translation == head.translation {
-            apple_eaten_event.write(AppleEaten);
+    for (entity, transform) in &apples {
+        if transform.translation == head.translation {
+            apple_eaten_event.write(AppleEaten {
+                entity,
+                cell: rules.board_cell(transform.translation.truncate()),
+            });
         }
     }
 }
@@ -240,20 +516,43 @@ fn check_eaten_apple(
 fn spawn_snake_part(
     mut commands: Commands,
     mut apple_eaten_event: EventReader<AppleEaten>,
-    snake_last: Res<SnakeLast>,
     snake_resources: Res<super::SnakeResourceManager>,
-    rng: Res<super::RngResource>,
-    id: Res<super::AtomicCounter>,
+    mut body: Single<&mut SnakeBody>,
+    mut occupancy: ResMut<Occupancy>,
+    rules: Res<GameRules>,
+) {
+    // Every new segment stacks on the vacated cell and unfolds as the snake moves
+    let growth = apple_eaten_event.read().count() * rules.growth;
+    for _ in 0..growth {
+        let length = body.len();
+        let entity = commands
+            .spawn((
+                SnakePart,
+                Transform::from_translation(rules.cell_position(body.vacated())),
+                Mesh3d(snake_resources.ball_mesh()),
+                MeshMaterial3d(snake_resources.segment_material(length, length + 1)),
+            ))
+            .id();
+        let segment = body.grow(entity);
+        occupancy.add_segment(segment.cell);
+    }
+}
+
+/// Some segment patterns depend on the snake length, so the whole body is repainted whenever it
+/// grows.
+fn paint_snake(
+    new_parts: Query<(), Added<SnakePart>>,
+    body: Single<&SnakeBody>,
+    mut parts: Query<&mut MeshMaterial3d<StandardMaterial>, With<SnakePart>>,
+    snake_resources: Res<super::SnakeResourceManager>,
 ) {
-    if let Some(_) = apple_eaten_event.read().last() {
-        commands.spawn((
-            SnakePart(id.get_id()),
-            Transform::from_translation(Vec3::new(snake_last.0.x, snake_last.0.y, INITIAL_Z)),
-            Mesh3d(snake_resources.ball_mesh()),
-            MeshMaterial3d(snake_resources.ball_material(
-                rng.random_in_range(0..(snake_resources.ball_materials_count() as u64)) as usize,
-            )),
-        ));
+    if new_parts.is_empty() {
+        return;
+    }
+    for (idx, segment) in body.iter().enumerate() {
+        if let Ok(mut material) = parts.get_mut(segment.entity) {
+            material.0 = segment_material(&body, idx, &snake_resources);
+        }
     }
 }
 
@@ -261,99 +560,277 @@ fn increase_fixed_update(
     snake_parts: Query<&SnakePart>,
     mut apple_eaten_event: EventReader<AppleEaten>,
     mut time: ResMut<Time<Fixed>>,
+    settings: Res<super::Settings>,
+    arena: Res<Arena>,
+    rules: Res<GameRules>,
+    mut speed_changed_event: EventWriter<SpeedChanged>,
 ) {
-    if let Some(_) = apple_eaten_event.read().last() {
-        let parts = snake_parts.iter().count() as f64;
-        let log_parts = parts.log2();
-        let mut hz = (10. + log_parts * 2.).next_up();
-        hz = hz.clamp(MIN_HZ, MAX_HZ);
-        time.set_timestep_hz(hz);
+    if apple_eaten_event.read().last().is_some() {
+        // The new segment isn't spawned yet
+        let pace = SpeedChanged::after_apple(
+            &arena,
+            &rules,
+            snake_parts.iter().count(),
+            settings.difficulty.speed_multiplier(),
+        );
+        time.set_timestep_hz(pace.hz);
+        speed_changed_event.write(pace);
     }
 }
 
 fn despawn_apple(
     mut commands: Commands,
     mut apple_eaten_event: EventReader<AppleEaten>,
-    apple: Single<Entity, With<Apple>>,
+    mut occupancy: ResMut<Occupancy>,
 ) {
-    if let Some(_) = apple_eaten_event.read().last() {
-        commands.entity(*apple).despawn();
+    for eaten in apple_eaten_event.read() {
+        // The head usually took the cell over already
+        if occupancy.get(eaten.cell) == Some(Occupant::Apple(eaten.entity)) {
+            occupancy.set(eaten.cell, None);
+        }
+        commands.entity(eaten.entity).try_despawn();
     }
 }
 
+/// Keeps as many apples on the board as the rules ask for, eaten ones are replaced on the frame
+/// they are despawned
 fn spawn_apple(
     mut commands: Commands,
-    apple: Option<Single<(Entity, &Apple)>>,
-    snake_parts: Query<&Transform, With<SnakePart>>,
+    apples: Query<(), With<Apple>>,
+    mut occupancy: ResMut<Occupancy>,
     rng: Res<super::RngResource>,
     snake_resources: Res<super::SnakeResourceManager>,
+    rules: Res<GameRules>,
 ) {
-    if apple.is_none() {
-        let mut all_pos_vec = Vec::new();
-        for i in (0..=PLAY_SIDE as u64) {
-            for j in (0..=PLAY_SIDE as u64) {
-                all_pos_vec.push(Vec3::new(
-                    i as f32 - HALF_PLAY_SIDE,
-                    j as f32 - HALF_PLAY_SIDE,
-                    INITIAL_Z,
-                ));
-            }
+    for _ in apples.iter().count()..rules.apples {
+        // A full board is a win, check_game_over takes care of it
+        let Some(cell) = occupancy.random_free_cell(&rng) else {
+            return;
+        };
+        let apple = commands
+            .spawn((
+                Apple,
+                Transform::from_translation(rules.cell_position(cell)),
+                Mesh3d(snake_resources.apple_mesh()),
+                MeshMaterial3d(snake_resources.apple_materials(
+                    rng.random_in_range(0..(snake_resources.apple_materials_count() as u64))
+                        as usize,
+                )),
+            ))
+            .id();
+        occupancy.set(cell, Some(Occupant::Apple(apple)));
+    }
+}
+
+fn check_game_over(
+    snake: Single<(&SnakeHead, &SnakeBody)>,
+    occupancy: Res<Occupancy>,
+    mode: Res<super::GameMode>,
+    rules: Res<GameRules>,
+    mut wall_hit_event: EventWriter<WallHit>,
+    mut game_end_event: EventWriter<GameEnd>,
+) {
+    // Checked first, a snake covering the whole board can only move into its own body
+    let (head, body) = *snake;
+    if occupancy.is_filled() {
+        game_end_event.write(GameEnd::new(EndCause::BoardFilled));
+        return;
+    }
+    match find_collision(head, body, &occupancy, &rules) {
+        Some(GameEnd {
+            cause: EndCause::Wall | EndCause::Obstacle,
+            ..
+        }) if mode.respawns_on_wall() => {
+            wall_hit_event.write(WallHit);
         }
-        let mut snake_parts_vec = Vec::new();
-        for part in snake_parts {
-            snake_parts_vec.push(part.translation);
+        Some(mut end) => {
+            if end.cause == EndCause::SelfBite {
+                // Only once per run, a scan is fine
+                end.entity = body
+                    .iter()
+                    .find(|segment| Some(segment.cell) == end.cell)
+                    .map(|segment| segment.entity);
+            }
+            game_end_event.write(end);
         }
-        // O(m*n)
-        let available_spots: Vec<_> = all_pos_vec
-            .into_iter()
-            .filter(|pos| {
-                if snake_parts_vec.contains(pos) {
-                    return false;
-                }
-                return true;
-            })
-            .collect();
-        commands.spawn((
-            Apple,
-            Transform::from_translation(
-                available_spots[rng.random_in_range(0..available_spots.len() as u64) as usize],
-            ),
-            Mesh3d(snake_resources.apple_mesh()),
-            MeshMaterial3d(snake_resources.apple_materials(0)),
-        ));
+        None => {}
     }
 }
 
-fn check_game_over(
-    head: Single<&SnakeHead>,
-    parts: Query<(&Transform, &SnakePart)>,
-    collidable_others: Query<
-        &Transform,
-        (
-            Without<Apple>,
-            Without<SnakePart>,
-            Without<DirectionalLight>,
-            Without<Camera3d>,
-        ),
-    >,
+/// Every way a run can end goes through GameEnd, the first one wins.
+fn end_run(
+    mut game_end_event: EventReader<GameEnd>,
+    mut last_game_end: ResMut<LastGameEnd>,
     mut next_state: ResMut<NextState<super::GameState>>,
 ) {
-    let parts_count = parts.iter().count();
-    let collision = collision(head, parts, collidable_others);
-    if (parts_count == PLAY_SIDE.powi(2) as usize) || collision {
-        next_state.set(super::GameState::Gameover);
+    if let Some(end) = game_end_event.read().next() {
+        info!(
+            "Run ended by {:?} at {:?}, hitting {:?}",
+            end.cause, end.cell, end.entity
+        );
+        last_game_end.0 = Some(*end);
+        next_state.set(if end.cause == EndCause::BoardFilled {
+            super::GameState::Victory
+        } else {
+            super::GameState::Gameover
+        });
+    }
+}
+
+fn reset_last_game_end(mut last_game_end: ResMut<LastGameEnd>) {
+    last_game_end.0 = None;
+}
+
+/// Moves the whole snake to a free cell with a runway ahead, see find_spawn. The body is stacked
+/// on the head and unfolds as it moves.
+fn respawn_snake(
+    mut wall_hit_event: EventReader<WallHit>,
+    snake: Single<(&mut SnakeHead, &mut SnakeBody)>,
+    mut parts: Query<&mut Transform, With<SnakePart>>,
+    rng: Res<super::RngResource>,
+    rules: Res<GameRules>,
+    mut occupancy: ResMut<Occupancy>,
+    mut game_end_event: EventWriter<GameEnd>,
+) {
+    if wall_hit_event.read().last().is_none() {
+        return;
+    }
+    let Some((cells, direction)) = find_spawn(&occupancy, &rng, 1) else {
+        warn!("No free cell to respawn the snake on, the wall ends the run");
+        game_end_event.write(GameEnd::new(EndCause::Wall));
+        return;
+    };
+    let cell = cells[0];
+    let (mut head, mut body) = snake.into_inner();
+    for segment in body.iter() {
+        occupancy.remove_segment(segment.cell);
+        occupancy.add_segment(cell);
+    }
+    body.stack(cell);
+    head.0 = direction.as_vec2();
+    let position = rules.cell_position(cell);
+    for mut transform in &mut parts {
+        transform.translation = position;
     }
 }
 
 fn despawn_all(
     mut commands: Commands,
-    all: Query<(Entity, &Transform)>,
+    all: Query<(Entity, &Transform), Without<Node>>,
     camera: Single<Entity, With<Camera3d>>,
+    rules: Res<GameRules>,
 ) {
     for (entity, transform) in all {
-        if transform.translation.z == INITIAL_Z {
+        if transform.translation.z == rules.depth() {
             commands.entity(entity).despawn();
         }
     }
     commands.entity(*camera).despawn();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::RngResource;
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// An empty board of `side`, walled in
+    fn board(side: i32) -> Occupancy {
+        let mut occupancy = Occupancy::new(side);
+        for y in -1..=side + 1 {
+            for x in -1..=side + 1 {
+                if x < 0 || y < 0 || x > side || y > side {
+                    occupancy.set(IVec2::new(x, y), Some(Occupant::Wall(Entity::PLACEHOLDER)));
+                }
+            }
+        }
+        occupancy
+    }
+
+    fn runway(occupancy: &Occupancy, head: IVec2, direction: IVec2) -> usize {
+        (1..=SPAWN_RUNWAY)
+            .take_while(|step| occupancy.get(head + direction * step).is_none())
+            .count()
+    }
+
+    #[test]
+    fn spawn_leaves_a_full_runway() {
+        let occupancy = board(GameRules::DEFAULT.side);
+        for seed in 0..50 {
+            let (cells, direction) = find_spawn(&occupancy, &RngResource::seeded(seed), 4).unwrap();
+            assert_eq!(
+                runway(&occupancy, cells[0], direction),
+                SPAWN_RUNWAY as usize
+            );
+            let laid_out: Vec<IVec2> = (0..4).map(|step| cells[0] - direction * step).collect();
+            assert_eq!(cells, laid_out);
+        }
+    }
+
+    #[test]
+    fn room_for_the_body_comes_before_the_runway() {
+        // On a 5x5 board a body of 5 only fits along a whole row or column, facing a wall
+        let occupancy = board(4);
+        for seed in 0..50 {
+            let (cells, direction) = find_spawn(&occupancy, &RngResource::seeded(seed), 5).unwrap();
+            let laid_out: Vec<IVec2> = (0..5).map(|step| cells[0] - direction * step).collect();
+            assert_eq!(cells, laid_out);
+            assert!(cells.iter().all(|cell| occupancy.get(*cell).is_none()));
+        }
+    }
+
+    #[test]
+    fn body_is_stacked_when_it_fits_nowhere() {
+        let occupancy = board(2);
+        let (cells, direction) = find_spawn(&occupancy, &RngResource::seeded(1), 6).unwrap();
+        assert_eq!(cells, [cells[0]; 6]);
+        assert_eq!(runway(&occupancy, cells[0], direction), 2);
+    }
+
+    #[test]
+    fn full_board_has_no_spawn() {
+        let mut occupancy = board(2);
+        for cell in occupancy.free_cells().to_vec() {
+            occupancy.add_segment(cell);
+        }
+        assert!(find_spawn(&occupancy, &RngResource::seeded(1), 1).is_none());
+    }
+
+    #[test]
+    fn a_wall_hit_with_nowhere_to_respawn_ends_the_run() {
+        let rules = GameRules {
+            side: 1,
+            ..GameRules::DEFAULT
+        };
+        let mut world = World::new();
+        world.init_resource::<Events<WallHit>>();
+        world.init_resource::<Events<GameEnd>>();
+        world.insert_resource(RngResource::seeded(0));
+        world.insert_resource(rules);
+        let mut occupancy = board(rules.side);
+        let head = world.spawn(SnakePart).id();
+        let mut body = SnakeBody::new(head, IVec2::new(0, 0));
+        occupancy.add_segment(IVec2::new(0, 0));
+        for cell in [IVec2::new(0, 1), IVec2::new(1, 1)] {
+            let part = world.spawn(SnakePart).id();
+            body.push(part, cell);
+            occupancy.add_segment(cell);
+        }
+        occupancy.set(IVec2::new(1, 0), Some(Occupant::Apple(Entity::PLACEHOLDER)));
+        world.insert_resource(occupancy);
+        world
+            .entity_mut(head)
+            .insert((SnakeHead::new(Vec2::NEG_X), body));
+
+        world.send_event(WallHit);
+        world.run_system_once(respawn_snake).unwrap();
+        let ends: Vec<EndCause> = world
+            .resource_mut::<Events<GameEnd>>()
+            .drain()
+            .map(|end| end.cause)
+            .collect();
+        assert_eq!(ends, [EndCause::Wall]);
+        let body = world.get::<SnakeBody>(head).unwrap();
+        assert_eq!(body.head().cell, IVec2::new(0, 0));
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+
+use super::GameMode;
+use super::localization::Localization;
+use super::score::{HighScores, RunTime, Score};
+use super::time_attack::TimeLeft;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(super::GameState::Gameplay), spawn_hud)
+            .add_systems(
+                Update,
+                update_hud.run_if(in_state(super::GameState::Gameplay)),
+            )
+            .add_systems(OnExit(super::GameState::Gameplay), despawn_hud);
+    }
+}
+
+/// Root of the in-game overlay. Screens that sweep every UI node away must skip it, it only
+/// goes away together with the gameplay state.
+#[derive(Component)]
+pub struct Hud;
+
+#[derive(Component, Clone, Copy)]
+enum HudField {
+    Score,
+    Length,
+    Speed,
+    Time,
+    Best,
+}
+
+const FIELDS: [HudField; 5] = [
+    HudField::Score,
+    HudField::Length,
+    HudField::Speed,
+    HudField::Time,
+    HudField::Best,
+];
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            Hud,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                top: Val::Px(10.),
+                justify_content: JustifyContent::SpaceEvenly,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for field in FIELDS {
+                parent.spawn((
+                    field,
+                    Text::default(),
+                    TextColor(Color::srgb(1., 1., 1.)),
+                    TextShadow::default(),
+                ));
+            }
+        });
+}
+
+fn format_time(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_hud(
+    fields: Query<(&mut Text, &HudField)>,
+    parts: Query<&super::SnakePart>,
+    score: Res<Score>,
+    high_scores: Res<HighScores>,
+    run_time: Res<RunTime>,
+    fixed_time: Res<Time<Fixed>>,
+    localization: Res<Localization>,
+    mode: Res<GameMode>,
+    time_left: Res<TimeLeft>,
+) {
+    for (mut text, field) in fields {
+        let value = match field {
+            HudField::Score => localization.format("hud.score", &[&score.0.to_string()]),
+            HudField::Length => {
+                localization.format("hud.length", &[&parts.iter().count().to_string()])
+            }
+            HudField::Speed => localization.format(
+                "hud.speed",
+                &[&format!("{:.1}", 1. / fixed_time.timestep().as_secs_f64())],
+            ),
+            // Time Attack counts down instead, rounding up so zero is only shown at the very end
+            HudField::Time if *mode == GameMode::TimeAttack => localization.format(
+                "hud.time_left",
+                &[&format_time(time_left.0.as_secs_f64().ceil() as u64)],
+            ),
+            HudField::Time => {
+                localization.format("hud.time", &[&format_time(run_time.0.as_secs())])
+            }
+            HudField::Best => {
+                let best = high_scores
+                    .best(*mode)
+                    .map_or_else(|| "-".to_owned(), |best| best.to_string());
+                localization.format("hud.best", &[&best])
+            }
+        };
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+fn despawn_hud(mut commands: Commands, hud: Query<Entity, With<Hud>>) {
+    for hud in hud {
+        commands.entity(hud).despawn();
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::asset::LoadState;
+use bevy::gltf::{Gltf, GltfMesh};
+use bevy::prelude::*;
+
+use super::localization::LocalizedText;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadedModels>()
+            .add_systems(
+                OnEnter(super::GameState::Loading),
+                (start_loading, build_ui, spawn_camera),
+            )
+            .add_systems(
+                Update,
+                check_loading.run_if(in_state(super::GameState::Loading)),
+            )
+            .add_systems(
+                OnExit(super::GameState::Loading),
+                (despawn_ui, despawn_camera),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Model {
+    Apple,
+    Head,
+}
+
+const MODELS: [Model; 2] = [Model::Apple, Model::Head];
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Model {
+    fn path(self: &Self) -> &'static str {
+        match self {
+            Model::Apple => "models/apple.gltf",
+            Model::Head => "models/snake_head.gltf",
+        }
+    }
+}
+
+/// Meshes taken from the glTF models. A None means the model couldn't be loaded and the
+/// SnakeResourceManager keeps using its primitive mesh instead.
+#[derive(Resource, Default)]
+pub struct LoadedModels {
+    pub apple: Option<Handle<Mesh>>,
+    pub head: Option<Handle<Mesh>>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl LoadedModels {
+    fn set(self: &mut Self, model: Model, mesh: Handle<Mesh>) {
+        match model {
+            Model::Apple => self.apple = Some(mesh),
+            Model::Head => self.head = Some(mesh),
+        }
+    }
+}
+
+/// Models still being loaded, they leave the list once loaded or failed.
+#[derive(Resource)]
+struct PendingModels(Vec<(Model, Handle<Gltf>)>);
+
+#[derive(Component)]
+struct ProgressBar;
+
+fn start_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let pending = MODELS
+        .iter()
+        .map(|model| (*model, asset_server.load(model.path())))
+        .collect();
+    commands.insert_resource(PendingModels(pending));
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+fn build_ui(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(40.),
+            height: Val::Percent(12.),
+            left: Val::Percent(30.),
+            top: Val::Percent(44.),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![
+            (
+                LocalizedText("loading.title"),
+                Text::default(),
+                TextColor(Color::srgb(1., 1., 1.)),
+                TextShadow::default()
+            ),
+            (
+                Node {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(35.),
+                    border: UiRect::all(Val::Px(3.0)),
+                    ..default()
+                },
+                BorderColor(Color::BLACK),
+                BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+                children![(
+                    ProgressBar,
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.8, 0.3)),
+                )]
+            )
+        ],
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    mut pending: ResMut<PendingModels>,
+    mut loaded: ResMut<LoadedModels>,
+    mut progress_bar: Single<&mut Node, With<ProgressBar>>,
+    mut next_state: ResMut<NextState<super::GameState>>,
+) {
+    pending
+        .0
+        .retain(|(model, handle)| match asset_server.load_state(handle) {
+            LoadState::Loaded => {
+                let mesh = gltfs
+                    .get(handle)
+                    .and_then(|gltf| gltf.meshes.first())
+                    .and_then(|mesh| gltf_meshes.get(mesh))
+                    .and_then(|mesh| mesh.primitives.first())
+                    .map(|primitive| primitive.mesh.clone());
+                match mesh {
+                    Some(mesh) => loaded.set(*model, mesh),
+                    None => warn!(
+                        "{} has no mesh, using the default {:?} mesh",
+                        model.path(),
+                        model
+                    ),
+                }
+                false
+            }
+            LoadState::Failed(error) => {
+                warn!(
+                    "Failed to load {}, using the default {:?} mesh: {}",
+                    model.path(),
+                    model,
+                    error
+                );
+                false
+            }
+            LoadState::NotLoaded | LoadState::Loading => true,
+        });
+
+    let done = MODELS.len() - pending.0.len();
+    progress_bar.width = Val::Percent(done as f32 / MODELS.len() as f32 * 100.);
+
+    if pending.0.is_empty() {
+        commands.remove_resource::<PendingModels>();
+        // Rebuilt so the meshes that were just loaded are picked up
+        commands.queue(|world: &mut World| {
+            let snake_resources = super::SnakeResourceManager::from_world(world);
+            world.insert_resource(snake_resources);
+        });
+        next_state.set(super::GameState::Main);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_ui(
+    mut commands: Commands,
+    nodes: Query<Entity, (With<Node>, Without<ChildOf>, Without<super::hud::Hud>)>,
+) {
+    for node in nodes {
+        commands.entity(node).despawn();
+    }
+}
+
+fn despawn_camera(mut commands: Commands, camera: Query<Entity, With<Camera2d>>) {
+    for camera in camera {
+        commands.entity(camera).despawn();
+    }
+}
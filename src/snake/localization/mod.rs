@@ -0,0 +1,135 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Localization>()
+            .add_systems(Startup, load_font)
+            .add_systems(
+                Update,
+                (sync_language, refresh_localized_text.after(sync_language)),
+            );
+    }
+}
+
+const LOCALES_DIR: &str = "assets/locales";
+/// The default bevy font only covers ASCII, which isn't enough for portuguese.
+const FONT_PATH: &str = "assets/fonts/DejaVuSans.ttf";
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    EnUs,
+    PtBr,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Language {
+    pub const ALL: [Language; 2] = [Language::EnUs, Language::PtBr];
+
+    pub fn code(self: &Self) -> &'static str {
+        match self {
+            Language::EnUs => "en-US",
+            Language::PtBr => "pt-BR",
+        }
+    }
+}
+
+/// Keys missing from the current language fall back to english, and then to the key itself so
+/// missing translations are easy to spot.
+#[derive(Resource)]
+pub struct Localization {
+    language: Language,
+    translations: HashMap<Language, HashMap<String, String>>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Localization {
+    pub fn get(self: &Self, key: &str) -> String {
+        [self.language, Language::EnUs]
+            .iter()
+            .find_map(|language| self.translations.get(language)?.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_owned())
+    }
+
+    /// Same as get, replacing {0}, {1}... by the given arguments.
+    pub fn format(self: &Self, key: &str, args: &[&str]) -> String {
+        let mut text = self.get(key);
+        for (idx, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{idx}}}"), arg);
+        }
+        text
+    }
+}
+
+impl Localization {
+    /// Reads every locale file, for front-ends running without the plugin
+    pub fn load(language: Language) -> Self {
+        let base_path = FileAssetReader::get_base_path().join(LOCALES_DIR);
+        let mut translations = HashMap::new();
+        for language in Language::ALL {
+            let path = base_path.join(format!("{}.ron", language.code()));
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    ron::from_str::<HashMap<String, String>>(&content).map_err(|e| e.to_string())
+                });
+            match parsed {
+                Ok(entries) => {
+                    translations.insert(language, entries);
+                }
+                Err(error) => warn!("Failed to load {}: {}", path.display(), error),
+            }
+        }
+        Self {
+            language,
+            translations,
+        }
+    }
+}
+
+impl FromWorld for Localization {
+    fn from_world(world: &mut World) -> Self {
+        let language = world
+            .get_resource::<super::Settings>()
+            .map(|settings| settings.language)
+            .unwrap_or_default();
+        Self::load(language)
+    }
+}
+
+/// Text entities holding a static translation key. The text is (re)written every time the
+/// language changes, so they can be spawned with an empty Text.
+#[derive(Component)]
+pub struct LocalizedText(pub &'static str);
+
+fn load_font(mut fonts: ResMut<Assets<Font>>) {
+    let path = FileAssetReader::get_base_path().join(FONT_PATH);
+    match std::fs::read(&path).map(Font::try_from_bytes) {
+        Ok(Ok(font)) => fonts.insert(&Handle::<Font>::default(), font),
+        Ok(Err(error)) => warn!("Failed to parse {}: {}", path.display(), error),
+        Err(error) => warn!("Failed to read {}: {}", path.display(), error),
+    }
+}
+
+fn sync_language(settings: Res<super::Settings>, mut localization: ResMut<Localization>) {
+    if settings.is_changed() && localization.language != settings.language {
+        localization.language = settings.language;
+    }
+}
+
+fn refresh_localized_text(
+    localization: Res<Localization>,
+    texts: Query<(Ref<LocalizedText>, &mut Text)>,
+) {
+    for (localized, mut text) in texts {
+        if localization.is_changed() || localized.is_added() {
+            text.0 = localization.get(localized.0);
+        }
+    }
+}
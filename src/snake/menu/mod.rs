@@ -0,0 +1,410 @@
+use bevy::app::AppExit;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use super::focus::{BackButton, Focus, MenuButton, NORMAL_BORDER, NORMAL_BUTTON};
+use super::localization::Localization;
+
+/// Screens are declared as a list of items by the plugin owning them, through
+/// `App::add_menu`, and opened by pushing their id on the MenuStack. Only the screen on top of
+/// the stack is shown, going back pops it.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuStack>()
+            .init_resource::<Menus>()
+            .add_systems(
+                Update,
+                (rebuild_menu, activate_items, refresh_labels).chain(),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MenuId {
+    Main,
+    Modes,
+    Campaign,
+    GameOver,
+    Victory,
+    Achievements,
+    Stats,
+    Settings,
+    Versus,
+    VersusOver,
+    Pause,
+}
+
+pub enum MenuAction {
+    Push(MenuId),
+    Back,
+    Exit,
+    Run(fn(&mut World)),
+}
+
+/// Labels are localization keys. Values are read and written through plain functions, so they
+/// can live anywhere in the world (usually a resource).
+pub enum MenuItem {
+    Button {
+        label: &'static str,
+        action: MenuAction,
+    },
+    Toggle {
+        label: &'static str,
+        get: fn(&World) -> bool,
+        set: fn(&mut World, bool),
+    },
+    Slider {
+        label: &'static str,
+        get: fn(&World) -> f32,
+        set: fn(&mut World, f32),
+        min: f32,
+        max: f32,
+        step: f32,
+    },
+    /// Options are already localized, `get` returns the index of the selected one
+    Choice {
+        label: &'static str,
+        options: fn(&World) -> Vec<String>,
+        get: fn(&World) -> usize,
+        set: fn(&mut World, usize),
+    },
+    /// Plain text that can't be focused, already localized by the screen builder
+    Label(String),
+}
+
+pub struct MenuScreen {
+    pub title: Option<&'static str>,
+    pub items: Vec<MenuItem>,
+}
+
+type MenuBuilder = fn(&World) -> MenuScreen;
+
+#[derive(Resource, Default)]
+struct Menus(HashMap<MenuId, MenuBuilder>);
+
+pub trait AppMenuExt {
+    fn add_menu(&mut self, id: MenuId, builder: MenuBuilder) -> &mut Self;
+}
+
+impl AppMenuExt for App {
+    fn add_menu(&mut self, id: MenuId, builder: MenuBuilder) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<Menus>()
+            .0
+            .insert(id, builder);
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MenuStack(Vec<MenuId>);
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl MenuStack {
+    /// Replaces the whole stack by the given screen
+    pub fn open(self: &mut Self, id: MenuId) {
+        self.0.clear();
+        self.0.push(id);
+    }
+
+    pub fn push(self: &mut Self, id: MenuId) {
+        self.0.push(id);
+    }
+
+    pub fn pop(self: &mut Self) {
+        self.0.pop();
+    }
+
+    pub fn clear(self: &mut Self) {
+        self.0.clear();
+    }
+
+    pub fn top(self: &Self) -> Option<MenuId> {
+        self.0.last().copied()
+    }
+}
+
+#[derive(Component)]
+struct MenuRoot;
+
+#[derive(Component)]
+struct MenuItemIdx(usize);
+
+#[derive(Component)]
+struct MenuTitle;
+
+fn current_screen(world: &World) -> Option<MenuScreen> {
+    let id = world.resource::<MenuStack>().top()?;
+    let builder = world.resource::<Menus>().0.get(&id)?;
+    Some(builder(world))
+}
+
+fn rebuild_menu(world: &mut World) {
+    if !world.is_resource_changed::<MenuStack>() {
+        return;
+    }
+    let roots: Vec<Entity> = world
+        .query_filtered::<Entity, With<MenuRoot>>()
+        .iter(world)
+        .collect();
+    for root in roots {
+        world.entity_mut(root).despawn();
+    }
+    world.resource_mut::<Focus>().0 = None;
+    let Some(screen) = current_screen(world) else {
+        return;
+    };
+
+    world
+        .spawn((
+            MenuRoot,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    width: Val::Percent(30.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                })
+                .with_children(|column| {
+                    if screen.title.is_some() {
+                        column.spawn((
+                            MenuTitle,
+                            Text::default(),
+                            TextFont::from_font_size(40.),
+                            TextColor(Color::srgb(1., 1., 1.)),
+                            TextShadow::default(),
+                        ));
+                    }
+                    for (idx, item) in screen.items.iter().enumerate() {
+                        if let MenuItem::Label(_) = item {
+                            column.spawn((
+                                MenuItemIdx(idx),
+                                Node {
+                                    width: Val::Percent(100.),
+                                    justify_content: JustifyContent::Center,
+                                    ..default()
+                                },
+                                children![(
+                                    Text::default(),
+                                    TextColor(Color::srgb(1., 1., 1.)),
+                                    TextShadow::default()
+                                )],
+                            ));
+                            continue;
+                        }
+                        let mut button = column.spawn((
+                            Button,
+                            MenuButton,
+                            MenuItemIdx(idx),
+                            Node {
+                                width: Val::Percent(100.),
+                                height: Val::Px(56.),
+                                border: UiRect::all(Val::Px(5.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BorderColor(NORMAL_BORDER),
+                            BorderRadius::MAX,
+                            BackgroundColor(NORMAL_BUTTON),
+                            children![(
+                                Text::default(),
+                                TextColor(Color::srgb(1., 1., 1.)),
+                                TextShadow::default()
+                            )],
+                        ));
+                        if let MenuItem::Button {
+                            action: MenuAction::Back,
+                            ..
+                        } = item
+                        {
+                            button.insert(BackButton);
+                        }
+                    }
+                });
+        });
+}
+
+/// How a value item reacts: clicks move forward and wrap around, arrows move either way
+#[derive(Clone, Copy)]
+enum Step {
+    Activate,
+    Left,
+    Right,
+}
+
+fn activate_items(world: &mut World) {
+    let mut steps: Vec<(usize, Step)> = world
+        .query_filtered::<(&Interaction, &MenuItemIdx), Changed<Interaction>>()
+        .iter(world)
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, item)| (item.0, Step::Activate))
+        .collect();
+
+    let keys = world.resource::<ButtonInput<KeyCode>>();
+    let horizontal = if keys.just_pressed(KeyCode::ArrowLeft) {
+        Some(Step::Left)
+    } else if keys.just_pressed(KeyCode::ArrowRight) {
+        Some(Step::Right)
+    } else {
+        None
+    };
+    let focused = world
+        .resource::<Focus>()
+        .0
+        .and_then(|entity| world.get::<MenuItemIdx>(entity))
+        .map(|item| item.0);
+    if let (Some(step), Some(idx)) = (horizontal, focused) {
+        steps.push((idx, step));
+    }
+
+    if steps.is_empty() {
+        return;
+    }
+    let Some(screen) = current_screen(world) else {
+        return;
+    };
+    for (idx, step) in steps {
+        let Some(item) = screen.items.get(idx) else {
+            continue;
+        };
+        apply_step(world, item, step);
+    }
+}
+
+fn apply_step(world: &mut World, item: &MenuItem, step: Step) {
+    match item {
+        MenuItem::Button { action, .. } => {
+            if let Step::Activate = step {
+                run_action(world, action);
+            }
+        }
+        MenuItem::Toggle { get, set, .. } => {
+            let value = get(world);
+            set(world, !value);
+        }
+        MenuItem::Slider {
+            get,
+            set,
+            min,
+            max,
+            step: increment,
+            ..
+        } => {
+            let value = get(world);
+            let next = match step {
+                Step::Activate if value + increment > *max + f32::EPSILON => *min,
+                Step::Activate | Step::Right => (value + increment).min(*max),
+                Step::Left => (value - increment).max(*min),
+            };
+            // Avoids drifting away from the steps due to float errors
+            let next = ((next - min) / increment).round() * increment + min;
+            set(world, next);
+        }
+        MenuItem::Choice {
+            options, get, set, ..
+        } => {
+            let count = options(world).len();
+            if count == 0 {
+                return;
+            }
+            let current = get(world);
+            let next = match step {
+                Step::Activate | Step::Right => (current + 1) % count,
+                Step::Left => (current + count - 1) % count,
+            };
+            set(world, next);
+        }
+        MenuItem::Label(_) => {}
+    }
+}
+
+fn run_action(world: &mut World, action: &MenuAction) {
+    match action {
+        MenuAction::Push(id) => world.resource_mut::<MenuStack>().push(*id),
+        MenuAction::Back => world.resource_mut::<MenuStack>().pop(),
+        MenuAction::Exit => {
+            world.send_event(AppExit::Success);
+        }
+        MenuAction::Run(run) => run(world),
+    }
+}
+
+fn label(world: &World, item: &MenuItem) -> String {
+    let localization = world.resource::<Localization>();
+    match item {
+        MenuItem::Button { label, .. } => localization.get(label),
+        MenuItem::Label(text) => text.clone(),
+        MenuItem::Toggle { label, get, .. } => {
+            let value = if get(world) {
+                "common.on"
+            } else {
+                "common.off"
+            };
+            format!("{}: {}", localization.get(label), localization.get(value))
+        }
+        MenuItem::Slider {
+            label,
+            get,
+            min,
+            max,
+            ..
+        } => {
+            let percent = (get(world) - min) / (max - min) * 100.;
+            format!("{}: {:.0}%", localization.get(label), percent)
+        }
+        MenuItem::Choice {
+            label,
+            options,
+            get,
+            ..
+        } => {
+            let value = options(world).get(get(world)).cloned().unwrap_or_default();
+            format!("{}: {}", localization.get(label), value)
+        }
+    }
+}
+
+/// Values can change from anywhere, so the labels are refreshed every frame. Menus are small,
+/// and the text is only touched when it actually changes.
+fn refresh_labels(world: &mut World) {
+    let Some(screen) = current_screen(world) else {
+        return;
+    };
+    let mut labels: Vec<(Entity, String)> = world
+        .query::<(&MenuItemIdx, &Children)>()
+        .iter(world)
+        .filter_map(|(item, children)| {
+            let text = children.first()?;
+            Some((*text, label(world, screen.items.get(item.0)?)))
+        })
+        .collect();
+    if let Some(title) = screen.title {
+        let title = world.resource::<Localization>().get(title);
+        let titles: Vec<Entity> = world
+            .query_filtered::<Entity, With<MenuTitle>>()
+            .iter(world)
+            .collect();
+        labels.extend(titles.into_iter().map(|entity| (entity, title.clone())));
+    }
+    for (entity, label) in labels {
+        if let Some(mut text) = world.get_mut::<Text>(entity)
+            && text.0 != label
+        {
+            text.0 = label;
+        }
+    }
+}
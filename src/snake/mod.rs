@@ -1,24 +1,68 @@
-use atomic_counter_resource::AtomicCounter;
-use bevy::prelude::*;
-use gameplay::Apple;
-use gameplay::PLAY_SIDE;
-use gameplay::SnakeHead;
+use game_mode::GameMode;
+use gameplay::AppleEaten;
 use gameplay::SnakePart;
-use gameplay::collision;
 use rng_resource::RngResource;
 use snake_resource_manager::SnakeResourceManager;
 use state::*;
 
-mod atomic_counter_resource;
+mod achievements;
+mod body;
+mod campaign;
 mod entrance;
+mod env;
+mod focus;
+mod game_mode;
 mod gameover;
 mod gameplay;
+mod hud;
+mod loading;
+mod localization;
 mod main_menu;
+mod menu;
+mod netplay;
+mod occupancy;
+mod pause;
+mod remote;
+mod replay;
 mod rng_resource;
+mod rules;
+mod score;
+mod settings;
 mod snake_resource_manager;
 mod state;
+mod stats;
+mod storage;
+mod theme;
+mod time_attack;
+mod versus;
+mod victory;
 
+pub use achievements::AchievementsPlugin;
+pub use body::{Segment, SnakeBody};
+pub use campaign::CampaignPlugin;
 pub use entrance::EntrancePlugin;
+pub use env::{
+    Action, EnvConfig, FEATURES, GRID_CHANNELS, Observation, ObservationKind, Rewards, SnakeEnv,
+    StepInfo, VecEnv,
+};
+pub use focus::FocusPlugin;
+pub use game_mode::GameModePlugin;
 pub use gameover::GameOverPlugin;
-pub use gameplay::GameplayPlugin;
+pub use gameplay::{Arena, EndCause, GameplayPlugin, SpeedChanged};
+pub use hud::HudPlugin;
+pub use loading::LoadingPlugin;
+pub use localization::{Localization, LocalizationPlugin};
 pub use main_menu::MainPlugin;
+pub use menu::MenuPlugin;
+pub use pause::PausePlugin;
+pub use remote::RemotePlugin;
+pub use replay::{ExportOptions, Frame, Replay, ReplayPlugin};
+pub use rules::{GameRules, RulesPlugin, RulesPresets, SpeedCurve, WallBehavior};
+pub use score::{POINTS_PER_APPLE, ScorePlugin};
+pub use settings::{AudioChannel, Settings, SettingsPlugin};
+pub use snake_resource_manager::Palette;
+pub use stats::StatsPlugin;
+pub use theme::{ThemePlugin, Themes};
+pub use time_attack::TimeAttackPlugin;
+pub use versus::VersusPlugin;
+pub use victory::VictoryPlugin;
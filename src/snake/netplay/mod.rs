@@ -0,0 +1,580 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time as the lockstep timeouts and the simulated link see it. Clones share the same time.
+#[derive(Clone)]
+pub enum Clock {
+    /// The wall clock
+    Real(Instant),
+    /// Only moves when advanced, so tests don't have to wait
+    #[cfg(test)]
+    Manual(Arc<Mutex<Duration>>),
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Clock {
+    pub fn real() -> Self {
+        Clock::Real(Instant::now())
+    }
+
+    #[cfg(test)]
+    pub fn manual() -> Self {
+        Clock::Manual(Arc::default())
+    }
+
+    /// Time since the clock was made
+    pub fn now(self: &Self) -> Duration {
+        match self {
+            Clock::Real(start) => start.elapsed(),
+            #[cfg(test)]
+            Clock::Manual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    /// Moves a manual clock forward, the real one moves on its own
+    #[cfg(test)]
+    pub fn advance(self: &Self, by: Duration) {
+        if let Clock::Manual(now) = self {
+            *now.lock().unwrap() += by;
+        }
+    }
+}
+
+/// Unreliable datagrams to the other peer. The lockstep protocol copes with lost, late and
+/// reordered packets on its own.
+#[allow(clippy::needless_arbitrary_self_type)]
+pub trait Transport: Send + Sync {
+    fn send(self: &mut Self, packet: &[u8]);
+    fn recv(self: &mut Self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    /// The host learns it from the first packet it gets
+    peer: Option<SocketAddr>,
+}
+
+impl UdpTransport {
+    pub fn host(port: u16) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peer: None })
+    }
+
+    pub fn join(peer: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            peer: Some(peer),
+        })
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Transport for UdpTransport {
+    fn send(self: &mut Self, packet: &[u8]) {
+        if let Some(peer) = self.peer
+            && let Err(error) = self.socket.send_to(packet, peer)
+        {
+            debug!("Failed to send to {}: {}", peer, error);
+        }
+    }
+
+    fn recv(self: &mut Self) -> Option<Vec<u8>> {
+        let mut buffer = [0; 65536];
+        // Errors are either WouldBlock or about a previous send, none of them is fatal
+        while let Ok((len, from)) = self.socket.recv_from(&mut buffer) {
+            match self.peer {
+                None => self.peer = Some(from),
+                Some(peer) if peer != from => continue,
+                Some(_) => {}
+            }
+            return Some(buffer[..len].to_vec());
+        }
+        None
+    }
+}
+
+/// How bad the simulated link is, applied on both directions
+#[derive(Clone, Copy)]
+pub struct LinkConditions {
+    /// Chance of dropping each packet, from 0 to 1
+    pub loss: f64,
+    pub latency: Duration,
+    /// Extra random delay on top of the latency, which also reorders packets
+    pub jitter: Duration,
+}
+
+/// Packets along with the time they are due
+type Queue = Arc<Mutex<VecDeque<(Duration, Vec<u8>)>>>;
+
+/// One end of an in-process link, for playing and testing without a network.
+pub struct SimulatedTransport {
+    outgoing: Queue,
+    incoming: Queue,
+    conditions: LinkConditions,
+    rng: StdRng,
+    clock: Clock,
+}
+
+impl SimulatedTransport {
+    pub fn pair(conditions: LinkConditions, seed: u64, clock: Clock) -> (Self, Self) {
+        let a = Queue::default();
+        let b = Queue::default();
+        (
+            Self {
+                outgoing: a.clone(),
+                incoming: b.clone(),
+                conditions,
+                rng: StdRng::seed_from_u64(seed),
+                clock: clock.clone(),
+            },
+            Self {
+                outgoing: b,
+                incoming: a,
+                conditions,
+                rng: StdRng::seed_from_u64(seed.wrapping_add(1)),
+                clock,
+            },
+        )
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Transport for SimulatedTransport {
+    fn send(self: &mut Self, packet: &[u8]) {
+        if self.rng.gen_bool(self.conditions.loss) {
+            return;
+        }
+        let delay = self.conditions.latency + self.conditions.jitter.mul_f64(self.rng.r#gen());
+        self.outgoing
+            .lock()
+            .unwrap()
+            .push_back((self.clock.now() + delay, packet.to_vec()));
+    }
+
+    fn recv(self: &mut Self) -> Option<Vec<u8>> {
+        let mut incoming = self.incoming.lock().unwrap();
+        let now = self.clock.now();
+        let idx = incoming.iter().position(|(due, _)| *due <= now)?;
+        incoming.remove(idx).map(|(_, packet)| packet)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    Join,
+    Welcome {
+        seed: u64,
+        input_delay: u32,
+        setup: String,
+    },
+    /// Every input from `first` on that the peer didn't acknowledge yet, so a lost packet is
+    /// covered by the next one
+    Inputs {
+        first: u32,
+        inputs: Vec<u8>,
+        /// How many inputs of the peer were received
+        ack: u32,
+        /// Latest state hash of the sender
+        checkpoint: Option<(u32, u64)>,
+    },
+    /// The host state, replacing the guest one after a desync. It isn't acknowledged: when it is
+    /// lost the next checkpoints still differ, and the host sends a new one.
+    Snapshot {
+        tick: u32,
+        state: String,
+    },
+    Bye,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+/// Ticks between the moment an input is made and the one it is applied on, hiding the latency.
+pub const INPUT_DELAY: u32 = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Bye is not acknowledged, sending it a few times makes it likely to get through
+const BYE_COPIES: usize = 3;
+
+/// Deterministic lockstep between two peers. Only inputs travel: each peer runs the same
+/// simulation and advances a tick once both inputs for it are known. Peers compare state hashes
+/// at checkpoints and the host sends its whole state when they differ, again at every checkpoint
+/// until they agree.
+///
+/// Inputs are opaque bytes, and the setup of the host and the state opaque strings: the
+/// simulation is up to the caller.
+pub struct Lockstep {
+    transport: Box<dyn Transport>,
+    clock: Clock,
+    role: Role,
+    seed: Option<u64>,
+    setup: Option<String>,
+    input_delay: u32,
+    /// Tick of the first input kept on both sides, the ones before it were played by both peers
+    base: u32,
+    local: VecDeque<u8>,
+    remote: VecDeque<u8>,
+    /// How many local inputs the peer has
+    acked: u32,
+    checkpoints: HashMap<u32, u64>,
+    last_checkpoint: Option<(u32, u64)>,
+    /// Latest tick the peer sent its state hash for
+    peer_checkpoint: Option<u32>,
+    resync_requested: bool,
+    last_resync: Option<u32>,
+    snapshot: Option<(u32, String)>,
+    started: Duration,
+    last_heard: Option<Duration>,
+    disconnected: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Lockstep {
+    pub fn host(
+        transport: Box<dyn Transport>,
+        clock: Clock,
+        seed: u64,
+        setup: String,
+        input_delay: u32,
+    ) -> Self {
+        let mut lockstep = Self::new(transport, clock, Role::Host);
+        lockstep.seed = Some(seed);
+        lockstep.setup = Some(setup);
+        lockstep.input_delay = input_delay;
+        lockstep
+    }
+
+    pub fn guest(transport: Box<dyn Transport>, clock: Clock) -> Self {
+        Self::new(transport, clock, Role::Guest)
+    }
+
+    fn new(transport: Box<dyn Transport>, clock: Clock, role: Role) -> Self {
+        Self {
+            transport,
+            started: clock.now(),
+            clock,
+            role,
+            seed: None,
+            setup: None,
+            input_delay: 0,
+            base: 0,
+            local: VecDeque::new(),
+            remote: VecDeque::new(),
+            acked: 0,
+            checkpoints: HashMap::default(),
+            last_checkpoint: None,
+            peer_checkpoint: None,
+            resync_requested: false,
+            last_resync: None,
+            snapshot: None,
+            last_heard: None,
+            disconnected: false,
+        }
+    }
+
+    pub fn role(self: &Self) -> Role {
+        self.role
+    }
+
+    /// Shared seed of the match, known once both peers are connected
+    pub fn seed(self: &Self) -> Option<u64> {
+        self.last_heard.and(self.seed)
+    }
+
+    /// Match settings of the host, known along with the seed
+    pub fn setup(self: &Self) -> Option<&str> {
+        self.last_heard.and(self.setup.as_deref())
+    }
+
+    pub fn is_disconnected(self: &Self) -> bool {
+        self.disconnected
+    }
+
+    /// Handles every packet that arrived, should be called once per tick
+    pub fn poll(self: &mut Self) {
+        while let Some(packet) = self.transport.recv() {
+            let Ok(message) = std::str::from_utf8(&packet)
+                .map_err(|error| error.to_string())
+                .and_then(|text| ron::from_str(text).map_err(|error| error.to_string()))
+            else {
+                debug!("Ignoring a malformed packet");
+                continue;
+            };
+            self.last_heard = Some(self.clock.now());
+            self.handle(message);
+        }
+        if self.seed().is_none() && self.role == Role::Guest {
+            self.post(&Message::Join);
+        }
+        let now = self.clock.now();
+        let silence = match self.last_heard {
+            Some(last_heard) => now - last_heard > DISCONNECT_TIMEOUT,
+            // The host waits for as long as it takes, it can always leave
+            None => self.role == Role::Guest && now - self.started > CONNECT_TIMEOUT,
+        };
+        if silence && !self.disconnected {
+            warn!("Lost the connection to the other peer");
+            self.disconnected = true;
+        }
+    }
+
+    fn handle(self: &mut Self, message: Message) {
+        match message {
+            Message::Join => {
+                if let Some(seed) = self.seed
+                    && let Some(setup) = self.setup.clone()
+                    && self.role == Role::Host
+                {
+                    if self.local_len() == 0 {
+                        self.local = vec![0; self.input_delay as usize].into();
+                    }
+                    self.post(&Message::Welcome {
+                        seed,
+                        input_delay: self.input_delay,
+                        setup,
+                    });
+                }
+            }
+            Message::Welcome {
+                seed,
+                input_delay,
+                setup,
+            } => {
+                if self.seed.is_none() && self.role == Role::Guest {
+                    self.seed = Some(seed);
+                    self.setup = Some(setup);
+                    self.input_delay = input_delay;
+                    self.local = vec![0; input_delay as usize].into();
+                }
+            }
+            Message::Inputs {
+                first,
+                inputs,
+                ack,
+                checkpoint,
+            } => {
+                self.acked = self.acked.max(ack.min(self.local_len()));
+                for (idx, input) in inputs.into_iter().enumerate() {
+                    if first + idx as u32 == self.remote_len() {
+                        self.remote.push_back(input);
+                    }
+                }
+                if let Some((tick, hash)) = checkpoint {
+                    self.compare_checkpoint(tick, hash);
+                }
+            }
+            Message::Snapshot { tick, state } => {
+                if self.role == Role::Guest {
+                    self.snapshot = Some((tick, state));
+                }
+            }
+            Message::Bye => {
+                info!("The other peer left");
+                self.disconnected = true;
+            }
+        }
+    }
+
+    fn compare_checkpoint(self: &mut Self, tick: u32, hash: u64) {
+        if self.peer_checkpoint.is_none_or(|latest| latest < tick) {
+            self.peer_checkpoint = Some(tick);
+            self.prune();
+        }
+        let Some(local) = self.checkpoints.get(&tick) else {
+            return;
+        };
+        if *local == hash || self.last_resync == Some(tick) {
+            return;
+        }
+        warn!("Desync detected at tick {}", tick);
+        self.last_resync = Some(tick);
+        self.resync_requested = self.role == Role::Host;
+    }
+
+    fn post(self: &mut Self, message: &Message) {
+        match ron::to_string(message) {
+            Ok(text) => self.transport.send(text.as_bytes()),
+            Err(error) => error!("Failed to serialize a packet: {}", error),
+        }
+    }
+
+    /// Queues the local input made at `tick`, unless it already has one. Returns whether it was
+    /// taken.
+    pub fn schedule(self: &mut Self, tick: u32, input: u8) -> bool {
+        if self.local_len() > tick + self.input_delay {
+            return false;
+        }
+        self.local.push_back(input);
+        true
+    }
+
+    fn local_len(self: &Self) -> u32 {
+        self.base + self.local.len() as u32
+    }
+
+    fn remote_len(self: &Self) -> u32 {
+        self.base + self.remote.len() as u32
+    }
+
+    /// Drops what both peers are done with: the inputs of the ticks both sides simulated past,
+    /// which the peer acknowledged, and the state hashes older than the latest one of the peer
+    fn prune(self: &mut Self) {
+        let (Some((local, _)), Some(peer)) = (self.last_checkpoint, self.peer_checkpoint) else {
+            return;
+        };
+        let base = local
+            .min(peer)
+            .min(self.acked)
+            .min(self.remote_len())
+            .max(self.base);
+        self.local.drain(..(base - self.base) as usize);
+        self.remote.drain(..(base - self.base) as usize);
+        self.base = base;
+        self.checkpoints.retain(|tick, _| *tick >= peer);
+    }
+
+    /// Sends the inputs the peer is missing, along with the latest checkpoint
+    pub fn send(self: &mut Self) {
+        if self.seed().is_none() {
+            return;
+        }
+        let message = Message::Inputs {
+            first: self.acked,
+            inputs: self
+                .local
+                .range((self.acked - self.base) as usize..)
+                .copied()
+                .collect(),
+            ack: self.remote_len(),
+            checkpoint: self.last_checkpoint,
+        };
+        self.post(&message);
+    }
+
+    /// Inputs of both peers for `tick`, the host one first. Ticks both peers played are
+    /// forgotten.
+    pub fn inputs(self: &Self, tick: u32) -> Option<[u8; 2]> {
+        let idx = tick.checked_sub(self.base)? as usize;
+        let local = *self.local.get(idx)?;
+        let remote = *self.remote.get(idx)?;
+        Some(match self.role {
+            Role::Host => [local, remote],
+            Role::Guest => [remote, local],
+        })
+    }
+
+    /// Records the state hash after `tick`, the peer compares it against its own
+    pub fn checkpoint(self: &mut Self, tick: u32, hash: u64) {
+        self.checkpoints.insert(tick, hash);
+        self.last_checkpoint = Some((tick, hash));
+        self.prune();
+    }
+
+    /// Whether the host should send its state, see send_snapshot
+    pub fn take_resync_request(self: &mut Self) -> bool {
+        std::mem::take(&mut self.resync_requested)
+    }
+
+    pub fn send_snapshot(self: &mut Self, tick: u32, state: String) {
+        self.post(&Message::Snapshot { tick, state });
+    }
+
+    /// State received from the host, which the guest has to load. A late one from before the
+    /// inputs that are kept can't be caught up from, the host sends a newer one.
+    pub fn take_snapshot(self: &mut Self) -> Option<(u32, String)> {
+        self.snapshot.take().filter(|(tick, _)| *tick >= self.base)
+    }
+
+    /// Tells the peer we are leaving, so it doesn't wait for the timeout
+    pub fn close(self: &mut Self) {
+        for _ in 0..BYE_COPIES {
+            self.post(&Message::Bye);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERFECT_LINK: LinkConditions = LinkConditions {
+        loss: 0.,
+        latency: Duration::ZERO,
+        jitter: Duration::ZERO,
+    };
+    const INTERVAL: u32 = 10;
+
+    /// Plays `ticks` rounds of a simulation that only counts ticks, returning how far each side
+    /// got
+    fn play(host: &mut Lockstep, guest: &mut Lockstep, clock: &Clock, ticks: u32) -> [u32; 2] {
+        let mut reached = [0; 2];
+        for _ in 0..ticks {
+            clock.advance(Duration::from_millis(100));
+            for (tick, peer) in reached.iter_mut().zip([&mut *host, &mut *guest]) {
+                peer.poll();
+                if peer.seed().is_none() {
+                    continue;
+                }
+                peer.schedule(*tick, (*tick % 5) as u8);
+                peer.send();
+                if peer.inputs(*tick).is_some() {
+                    *tick += 1;
+                    if tick.is_multiple_of(INTERVAL) {
+                        peer.checkpoint(*tick, *tick as u64);
+                    }
+                }
+            }
+        }
+        reached
+    }
+
+    #[test]
+    fn played_ticks_are_forgotten() {
+        let clock = Clock::manual();
+        let (host_link, guest_link) = SimulatedTransport::pair(PERFECT_LINK, 0, clock.clone());
+        let mut host = Lockstep::host(Box::new(host_link), clock.clone(), 1, String::new(), 3);
+        let mut guest = Lockstep::guest(Box::new(guest_link), clock.clone());
+        let reached = play(&mut host, &mut guest, &clock, 1000);
+        assert!(
+            reached.iter().all(|tick| *tick > 900),
+            "only got to {reached:?}"
+        );
+        for peer in [&host, &guest] {
+            assert!(peer.base > 900);
+            assert!(peer.local.len() < 3 * INTERVAL as usize);
+            assert!(peer.remote.len() < 3 * INTERVAL as usize);
+            assert!(peer.checkpoints.len() <= 2);
+            assert!(peer.inputs(0).is_none());
+        }
+        // Inputs still to be played are kept, and agree on both sides
+        let [tick, _] = reached;
+        assert_eq!(host.inputs(tick - 1), guest.inputs(tick - 1));
+        assert!(!host.is_disconnected() && !guest.is_disconnected());
+    }
+
+    #[test]
+    fn snapshots_older_than_the_kept_inputs_are_dropped() {
+        let clock = Clock::manual();
+        let (host_link, guest_link) = SimulatedTransport::pair(PERFECT_LINK, 0, clock.clone());
+        let mut host = Lockstep::host(Box::new(host_link), clock.clone(), 1, String::new(), 3);
+        let mut guest = Lockstep::guest(Box::new(guest_link), clock.clone());
+        play(&mut host, &mut guest, &clock, 100);
+        assert!(guest.base > 0);
+        guest.snapshot = Some((guest.base - 1, String::new()));
+        assert!(guest.take_snapshot().is_none());
+        guest.snapshot = Some((guest.base, String::new()));
+        assert!(guest.take_snapshot().is_some());
+    }
+}
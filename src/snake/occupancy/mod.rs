@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+
+/// What sits on a board cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Occupant {
+    Wall(Entity),
+    Obstacle(Entity),
+    /// Number of segments on the cell, more than one only while a respawned body unfolds
+    Snake(u32),
+    Apple(Entity),
+}
+
+/// Every cell of the board, border walls included, so lookups never need to scan entities.
+/// Playable cells go from (0, 0) to (max, max) and the border sits right outside of them.
+///
+/// Free playable cells are also kept in a list, so a random one can be picked in O(1): each cell
+/// knows its position in the list and removals swap the last entry in.
+#[derive(Resource, Clone)]
+pub struct Occupancy {
+    max: i32,
+    cells: Vec<Option<Occupant>>,
+    free: Vec<IVec2>,
+    free_idx: Vec<usize>,
+}
+
+const NOT_FREE: usize = usize::MAX;
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Occupancy {
+    pub fn new(max: i32) -> Self {
+        let side = (max + 3) as usize;
+        let mut occupancy = Self {
+            max,
+            cells: vec![None; side * side],
+            free: Vec::with_capacity((max as usize + 1).pow(2)),
+            free_idx: vec![NOT_FREE; side * side],
+        };
+        for y in 0..=max {
+            for x in 0..=max {
+                occupancy.mark_free(IVec2::new(x, y));
+            }
+        }
+        occupancy
+    }
+
+    fn index(self: &Self, cell: IVec2) -> Option<usize> {
+        let side = self.max + 3;
+        let (x, y) = (cell.x + 1, cell.y + 1);
+        if x < 0 || y < 0 || x >= side || y >= side {
+            return None;
+        }
+        Some((y * side + x) as usize)
+    }
+
+    fn is_playable(self: &Self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmple(IVec2::splat(self.max)).all()
+    }
+
+    fn mark_free(self: &mut Self, cell: IVec2) {
+        let idx = self.index(cell).unwrap();
+        if self.free_idx[idx] == NOT_FREE {
+            self.free_idx[idx] = self.free.len();
+            self.free.push(cell);
+        }
+    }
+
+    fn mark_taken(self: &mut Self, cell: IVec2) {
+        let idx = self.index(cell).unwrap();
+        let position = self.free_idx[idx];
+        if position == NOT_FREE {
+            return;
+        }
+        self.free.swap_remove(position);
+        if let Some(moved) = self.free.get(position).copied() {
+            let moved_idx = self.index(moved).unwrap();
+            self.free_idx[moved_idx] = position;
+        }
+        self.free_idx[idx] = NOT_FREE;
+    }
+
+    /// Cells outside of the grid read as empty
+    pub fn get(self: &Self, cell: IVec2) -> Option<Occupant> {
+        self.index(cell).and_then(|idx| self.cells[idx])
+    }
+
+    pub fn set(self: &mut Self, cell: IVec2, occupant: Option<Occupant>) {
+        let Some(idx) = self.index(cell) else {
+            return;
+        };
+        self.cells[idx] = occupant;
+        if self.is_playable(cell) {
+            match occupant {
+                Some(_) => self.mark_taken(cell),
+                None => self.mark_free(cell),
+            }
+        }
+    }
+
+    pub fn add_segment(self: &mut Self, cell: IVec2) {
+        let count = match self.get(cell) {
+            Some(Occupant::Snake(count)) => count + 1,
+            _ => 1,
+        };
+        self.set(cell, Some(Occupant::Snake(count)));
+    }
+
+    pub fn remove_segment(self: &mut Self, cell: IVec2) {
+        match self.get(cell) {
+            Some(Occupant::Snake(count)) if count > 1 => {
+                self.set(cell, Some(Occupant::Snake(count - 1)))
+            }
+            Some(Occupant::Snake(_)) => self.set(cell, None),
+            _ => {}
+        }
+    }
+
+    /// Free playable cells, in no particular order
+    pub fn free_cells(self: &Self) -> &[IVec2] {
+        &self.free
+    }
+
+    /// Every playable cell holds the snake or an obstacle, with no apple left to eat
+    pub fn is_filled(self: &Self) -> bool {
+        // Only scanned once no cell is free
+        self.free.is_empty()
+            && !self
+                .cells
+                .iter()
+                .any(|cell| matches!(cell, Some(Occupant::Apple(_))))
+    }
+
+    pub fn random_free_cell(self: &Self, rng: &super::RngResource) -> Option<IVec2> {
+        if self.free.is_empty() {
+            return None;
+        }
+        Some(self.free[rng.random_in_range(0..self.free.len() as u64) as usize])
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Occupancy {
+    /// The free list holds exactly the empty playable cells, each once and where its index says
+    pub fn assert_consistent(self: &Self) {
+        let mut empty = 0;
+        for y in -1..=self.max + 1 {
+            for x in -1..=self.max + 1 {
+                let cell = IVec2::new(x, y);
+                let position = self.free_idx[self.index(cell).unwrap()];
+                if self.is_playable(cell) && self.get(cell).is_none() {
+                    empty += 1;
+                    assert_eq!(self.free.get(position), Some(&cell));
+                } else {
+                    assert_eq!(position, NOT_FREE, "{cell} is in the free list");
+                }
+            }
+        }
+        assert_eq!(self.free.len(), empty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::RngResource;
+    use super::*;
+
+    const MAX: i32 = 4;
+
+    fn playable() -> impl Iterator<Item = IVec2> {
+        (0..=MAX).flat_map(|y| (0..=MAX).map(move |x| IVec2::new(x, y)))
+    }
+
+    #[test]
+    fn new_board_is_free_but_the_border() {
+        let occupancy = Occupancy::new(MAX);
+        assert_eq!(occupancy.free_cells().len(), 25);
+        occupancy.assert_consistent();
+    }
+
+    #[test]
+    fn fill_drain_and_refill() {
+        let rng = RngResource::seeded(0);
+        let mut occupancy = Occupancy::new(MAX);
+        for round in 0..2 {
+            for (i, cell) in playable().enumerate() {
+                if (i + round) % 2 == 0 {
+                    occupancy.add_segment(cell);
+                } else {
+                    occupancy.set(cell, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+                }
+                occupancy.assert_consistent();
+            }
+            assert!(occupancy.free_cells().is_empty());
+            assert_eq!(occupancy.random_free_cell(&rng), None);
+
+            // Out of order, so removals swap entries from the middle of the list
+            let mut cells: Vec<IVec2> = playable().collect();
+            cells.sort_by_key(|cell| (cell.x * 7 + cell.y * 3) % 5);
+            for (i, cell) in cells.into_iter().enumerate() {
+                occupancy.remove_segment(cell);
+                occupancy.set(cell, None);
+                occupancy.assert_consistent();
+                assert_eq!(occupancy.free_cells().len(), i + 1);
+                let free = occupancy.random_free_cell(&rng).unwrap();
+                assert_eq!(occupancy.get(free), None);
+            }
+        }
+    }
+
+    #[test]
+    fn stacked_segments_free_the_cell_once_all_leave() {
+        let mut occupancy = Occupancy::new(MAX);
+        let cell = IVec2::new(2, 3);
+        occupancy.add_segment(cell);
+        occupancy.add_segment(cell);
+        occupancy.remove_segment(cell);
+        assert_eq!(occupancy.get(cell), Some(Occupant::Snake(1)));
+        occupancy.assert_consistent();
+        occupancy.remove_segment(cell);
+        assert_eq!(occupancy.get(cell), None);
+        occupancy.assert_consistent();
+
+        // Only snake segments are removed
+        let apple = Some(Occupant::Apple(Entity::PLACEHOLDER));
+        occupancy.set(cell, apple);
+        occupancy.remove_segment(cell);
+        assert_eq!(occupancy.get(cell), apple);
+        occupancy.assert_consistent();
+    }
+
+    #[test]
+    fn border_and_outside_cells_stay_out_of_the_free_list() {
+        let mut occupancy = Occupancy::new(MAX);
+        let wall = Some(Occupant::Wall(Entity::PLACEHOLDER));
+        occupancy.set(IVec2::new(-1, 2), wall);
+        occupancy.set(IVec2::new(MAX + 1, MAX + 1), wall);
+        occupancy.set(IVec2::new(-5, 40), wall);
+        assert_eq!(occupancy.get(IVec2::new(-1, 2)), wall);
+        assert_eq!(occupancy.get(IVec2::new(-5, 40)), None);
+        occupancy.set(IVec2::new(-1, 2), None);
+        assert_eq!(occupancy.free_cells().len(), 25);
+        occupancy.assert_consistent();
+    }
+
+    #[test]
+    fn random_free_cell_finds_the_last_one() {
+        let rng = RngResource::seeded(3);
+        let mut occupancy = Occupancy::new(MAX);
+        let last = IVec2::new(3, 1);
+        for cell in playable().filter(|cell| *cell != last) {
+            occupancy.add_segment(cell);
+        }
+        for _ in 0..10 {
+            assert_eq!(occupancy.random_free_cell(&rng), Some(last));
+        }
+    }
+
+    #[test]
+    fn filled_needs_every_cell_taken_and_no_apple_left() {
+        let mut occupancy = Occupancy::new(1);
+        let cells = [IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(0, 1)];
+        for cell in cells {
+            occupancy.add_segment(cell);
+        }
+        // Stacked segments don't fill the last cell
+        occupancy.add_segment(cells[0]);
+        assert!(!occupancy.is_filled());
+        let last = IVec2::new(1, 1);
+        occupancy.set(last, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+        assert!(occupancy.free_cells().is_empty());
+        assert!(!occupancy.is_filled());
+        occupancy.add_segment(last);
+        assert!(occupancy.is_filled());
+    }
+}
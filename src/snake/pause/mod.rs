@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use bevy::window::{WindowFocused, WindowOccluded};
+
+use super::GameplayState;
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen, MenuStack};
+use super::remote::RemoteControl;
+
+/// Pausing with Escape or P, or on its own when the window loses focus, and the countdown the
+/// snake waits for at the start of a run and after every pause. Runs driven by the remote
+/// control skip both.
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<GameplayState>()
+            .init_resource::<Countdown>()
+            .add_menu(MenuId::Pause, pause_menu)
+            .add_systems(OnEnter(super::GameState::Gameplay), reset_countdown)
+            .add_systems(OnEnter(GameplayState::Countdown), spawn_countdown)
+            .add_systems(OnExit(GameplayState::Countdown), despawn_countdown)
+            .add_systems(OnEnter(GameplayState::Paused), open_menu)
+            .add_systems(OnExit(GameplayState::Paused), close_menu)
+            .add_systems(
+                Update,
+                (
+                    toggle_pause,
+                    pause_on_focus_loss,
+                    tick_countdown.run_if(in_state(GameplayState::Countdown)),
+                )
+                    .run_if(in_state(super::GameState::Gameplay)),
+            );
+    }
+}
+
+const COUNTDOWN_SECONDS: u32 = 3;
+
+#[derive(Resource)]
+struct Countdown {
+    timer: Timer,
+    /// Only at the start of a run, resuming just counts down
+    get_ready: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Countdown {
+    fn new(get_ready: bool) -> Self {
+        Self {
+            timer: Timer::from_seconds(COUNTDOWN_SECONDS as f32, TimerMode::Once),
+            get_ready,
+        }
+    }
+
+    fn text(self: &Self, localization: &Localization) -> String {
+        let left = COUNTDOWN_SECONDS - self.timer.elapsed_secs() as u32;
+        if self.get_ready {
+            format!("{}\n{}", localization.get("countdown.get_ready"), left)
+        } else {
+            left.to_string()
+        }
+    }
+}
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+#[derive(Component)]
+struct CountdownText;
+
+fn resume(world: &mut World) {
+    world.insert_resource(Countdown::new(false));
+    world
+        .resource_mut::<NextState<GameplayState>>()
+        .set(GameplayState::Countdown);
+}
+
+fn pause_menu(_world: &World) -> MenuScreen {
+    MenuScreen {
+        title: Some("pause.title"),
+        items: vec![
+            MenuItem::Button {
+                label: "pause.resume",
+                action: MenuAction::Run(resume),
+            },
+            MenuItem::Button {
+                label: "pause.main_menu",
+                action: MenuAction::Run(|world| {
+                    world
+                        .resource_mut::<NextState<super::GameState>>()
+                        .set(super::GameState::Main)
+                }),
+            },
+        ],
+    }
+}
+
+fn open_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.open(MenuId::Pause);
+}
+
+fn close_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.clear();
+}
+
+/// Runs before the countdown state is entered, parents enter first
+fn reset_countdown(mut commands: Commands) {
+    commands.insert_resource(Countdown::new(true));
+}
+
+fn toggle_pause(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameplayState>>,
+    mut next_state: ResMut<NextState<GameplayState>>,
+) {
+    if !input.any_just_pressed([KeyCode::Escape, KeyCode::KeyP]) {
+        return;
+    }
+    match state.get() {
+        GameplayState::Countdown | GameplayState::Running => next_state.set(GameplayState::Paused),
+        GameplayState::Paused => commands.queue(resume),
+    }
+}
+
+fn pause_on_focus_loss(
+    mut focused_events: EventReader<WindowFocused>,
+    mut occluded_events: EventReader<WindowOccluded>,
+    state: Res<State<GameplayState>>,
+    mut next_state: ResMut<NextState<GameplayState>>,
+    remote: Option<Res<RemoteControl>>,
+) {
+    let lost_focus = focused_events.read().any(|event| !event.focused);
+    let occluded = occluded_events.read().any(|event| event.occluded);
+    if (lost_focus || occluded) && remote.is_none() && *state.get() != GameplayState::Paused {
+        next_state.set(GameplayState::Paused);
+    }
+}
+
+fn spawn_countdown(
+    mut commands: Commands,
+    countdown: Res<Countdown>,
+    localization: Res<Localization>,
+    remote: Option<Res<RemoteControl>>,
+    mut next_state: ResMut<NextState<GameplayState>>,
+) {
+    if remote.is_some() {
+        next_state.set(GameplayState::Running);
+        return;
+    }
+    commands.spawn((
+        CountdownText,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![(
+            Text::new(countdown.text(&localization)),
+            TextFont::from_font_size(80.),
+            TextColor(Color::srgb(1., 1., 1.)),
+            TextShadow::default(),
+            TextLayout::new_with_justify(JustifyText::Center),
+        )],
+    ));
+}
+
+fn tick_countdown(
+    time: Res<Time>,
+    mut countdown: ResMut<Countdown>,
+    localization: Res<Localization>,
+    root: Single<&Children, With<CountdownText>>,
+    mut texts: Query<&mut Text>,
+    mut next_state: ResMut<NextState<GameplayState>>,
+) {
+    countdown.timer.tick(time.delta());
+    if countdown.timer.finished() {
+        next_state.set(GameplayState::Running);
+        return;
+    }
+    let value = countdown.text(&localization);
+    for child in root.iter() {
+        if let Ok(mut text) = texts.get_mut(child)
+            && text.0 != value
+        {
+            text.0 = value.clone();
+        }
+    }
+}
+
+fn despawn_countdown(mut commands: Commands, roots: Query<Entity, With<CountdownText>>) {
+    for root in &roots {
+        commands.entity(root).despawn();
+    }
+}
@@ -0,0 +1,482 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use super::body::SnakeBody;
+use super::gameplay::{Apple, EndCause, GameEnd, SnakeHead, SnakeMoved, SnakeStep, Wall};
+use super::rules::GameRules;
+use super::score::Score;
+
+/// Lets another program play: the board is streamed as JSON lines to every client of a localhost
+/// socket, and clients send commands back the same way. Only enabled when SNAKE_REMOTE_PORT is
+/// set, SNAKE_REMOTE_LOCKSTEP=1 makes the snake wait for a command before every move.
+///
+/// Commands are `{"turn": "up"}` (or down, left, right), `"straight"` and `"restart"`. The
+/// server sends `{"state": {...}}` after every move, `{"end": {...}}` when the run is over and
+/// `{"error": "..."}` for commands it couldn't read.
+pub struct RemotePlugin;
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let Ok(port) = std::env::var("SNAKE_REMOTE_PORT") else {
+            return;
+        };
+        let lockstep = std::env::var("SNAKE_REMOTE_LOCKSTEP").is_ok_and(|value| value == "1");
+        let listener = match listen(&port) {
+            Ok(listener) => listener,
+            Err(error) => {
+                error!("Failed to start the remote control on {}: {}", port, error);
+                return;
+            }
+        };
+        info!(
+            "Remote control listening on 127.0.0.1:{}{}",
+            port,
+            if lockstep { " in lockstep" } else { "" }
+        );
+        app.insert_resource(RemoteControl {
+            listener,
+            clients: Vec::new(),
+            lockstep,
+            commands: Vec::new(),
+            ready: false,
+            tick: 0,
+            needs_state: false,
+        })
+        .add_systems(PreUpdate, (accept_clients, read_commands).chain())
+        .add_systems(OnEnter(super::GameState::Gameplay), reset_ticks)
+        .add_systems(
+            FixedUpdate,
+            apply_commands
+                .before(SnakeStep)
+                .run_if(in_state(super::GameState::Gameplay))
+                .run_if(in_state(super::GameplayState::Running)),
+        )
+        .add_systems(Update, restart)
+        .add_systems(
+            PostUpdate,
+            (stream_state, stream_end).run_if(in_state(super::GameState::Gameplay)),
+        );
+    }
+}
+
+fn listen(port: &str) -> Result<TcpListener, String> {
+    let port = port.parse::<u16>().map_err(|error| error.to_string())?;
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|error| error.to_string())?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    Ok(listener)
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Direction {
+    fn vector(self: &Self) -> Vec2 {
+        match self {
+            Direction::Up => Vec2::Y,
+            Direction::Down => Vec2::NEG_Y,
+            Direction::Left => Vec2::NEG_X,
+            Direction::Right => Vec2::X,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Command {
+    Turn(Direction),
+    /// Moves on without turning, only meaningful in lockstep
+    Straight,
+    /// Starts a new run of the same mode once the current one is over, ignored during a run
+    Restart,
+}
+
+#[derive(Serialize)]
+struct BoardState {
+    /// Moves made in the current run
+    tick: u64,
+    head: IVec2,
+    direction: IVec2,
+    /// Without the head, from the neck to the tail
+    body: Vec<IVec2>,
+    apples: Vec<IVec2>,
+    walls: Vec<IVec2>,
+    score: u32,
+    /// Moves per second
+    hz: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Line<'a> {
+    State(BoardState),
+    End {
+        cause: EndCause,
+        cell: Option<IVec2>,
+    },
+    Error(&'a str),
+}
+
+struct Client {
+    stream: TcpStream,
+    /// Bytes read that don't make a whole line yet
+    pending: Vec<u8>,
+    closed: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Client {
+    fn send(self: &mut Self, line: &str) {
+        let result = self
+            .stream
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stream.write_all(b"\n"));
+        // A client too slow to keep up is dropped, the game never waits on a socket
+        if let Err(error) = result {
+            warn!("Dropping a remote control client: {}", error);
+            self.closed = true;
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct RemoteControl {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    lockstep: bool,
+    commands: Vec<Command>,
+    /// Whether the current fixed step took a command
+    ready: bool,
+    /// Moves made in the current run
+    tick: u64,
+    needs_state: bool,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl RemoteControl {
+    fn broadcast(self: &mut Self, line: &Line) {
+        let Ok(line) = serde_json::to_string(line) else {
+            return;
+        };
+        for client in &mut self.clients {
+            client.send(&line);
+        }
+    }
+}
+
+/// Run condition of the snake move: in lockstep it waits for a command, otherwise it always goes
+pub fn may_step(remote: Option<Res<RemoteControl>>) -> bool {
+    remote.is_none_or(|remote| !remote.lockstep || remote.ready)
+}
+
+fn accept_clients(mut remote: ResMut<RemoteControl>) {
+    while let Ok((stream, address)) = remote.listener.accept() {
+        if let Err(error) = stream.set_nonblocking(true) {
+            warn!(
+                "Failed to set up remote control client {}: {}",
+                address, error
+            );
+            continue;
+        }
+        info!("Remote control client connected from {}", address);
+        remote.clients.push(Client {
+            stream,
+            pending: Vec::new(),
+            closed: false,
+        });
+        // The new client needs a board to start from
+        remote.needs_state = true;
+    }
+}
+
+fn read_commands(mut remote: ResMut<RemoteControl>) {
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+    for client in &mut remote.clients {
+        let mut buffer = [0; 4096];
+        loop {
+            match client.stream.read(&mut buffer) {
+                Ok(0) => {
+                    client.closed = true;
+                    break;
+                }
+                Ok(len) => client.pending.extend_from_slice(&buffer[..len]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    client.closed = true;
+                    break;
+                }
+            }
+        }
+        for parsed in take_lines(&mut client.pending) {
+            match parsed {
+                Ok(command) => commands.push(command),
+                Err(error) => errors.push(error),
+            }
+        }
+    }
+    remote.clients.retain(|client| !client.closed);
+    remote.commands.extend(commands);
+    for error in errors {
+        remote.broadcast(&Line::Error(&error));
+    }
+}
+
+/// Parses every whole line in `pending`, leaving the start of the next one there
+fn take_lines(pending: &mut Vec<u8>) -> Vec<Result<Command, String>> {
+    let mut parsed = Vec::new();
+    while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = pending.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        parsed.push(serde_json::from_str::<Command>(&line).map_err(|error| error.to_string()));
+    }
+    parsed
+}
+
+fn reset_ticks(mut remote: ResMut<RemoteControl>) {
+    // Whatever was sent during the previous run or the menus is stale
+    remote.commands.clear();
+    remote.tick = 0;
+    remote.needs_state = true;
+}
+
+/// In lockstep every move takes one command, otherwise commands apply as soon as they arrive.
+/// Taking a command doesn't mean the snake moves, stream_state counts the actual moves.
+fn apply_commands(mut remote: ResMut<RemoteControl>, mut head: Single<&mut SnakeHead>) {
+    let taken = if remote.lockstep {
+        remote.commands.len().min(1)
+    } else {
+        remote.commands.len()
+    };
+    let mut ready = false;
+    for command in remote.commands.drain(..taken).collect::<Vec<_>>() {
+        match command {
+            Command::Turn(direction) => head.turn(direction.vector()),
+            Command::Straight => {}
+            Command::Restart => continue,
+        }
+        ready = true;
+    }
+    remote.ready = ready;
+}
+
+fn restart(
+    mut commands: Commands,
+    mut remote: ResMut<RemoteControl>,
+    state: Res<State<super::GameState>>,
+) {
+    let Some(idx) = remote
+        .commands
+        .iter()
+        .position(|command| matches!(command, Command::Restart))
+    else {
+        return;
+    };
+    if matches!(
+        state.get(),
+        super::GameState::Gameover | super::GameState::Victory
+    ) {
+        remote.commands.remove(idx);
+        commands.queue(super::gameover::retry);
+    }
+}
+
+/// Sent from PostUpdate, so the apples and the score already account for the last move
+#[allow(clippy::too_many_arguments)]
+fn stream_state(
+    mut remote: ResMut<RemoteControl>,
+    mut snake_moved_event: EventReader<SnakeMoved>,
+    snake: Option<Single<(&SnakeHead, &SnakeBody)>>,
+    apples: Query<&Transform, With<Apple>>,
+    walls: Query<&Transform, With<Wall>>,
+    score: Res<Score>,
+    fixed_time: Res<Time<Fixed>>,
+    rules: Res<GameRules>,
+) {
+    let moves = snake_moved_event.read().count() as u64;
+    remote.tick += moves;
+    if moves == 0 && !remote.needs_state {
+        return;
+    }
+    let Some(snake) = snake else {
+        return;
+    };
+    let (head, body) = *snake;
+    remote.needs_state = false;
+    let cell = |transform: &Transform| rules.board_cell(transform.translation.truncate());
+    let state = BoardState {
+        tick: remote.tick,
+        head: body.head().cell,
+        direction: head.direction().as_ivec2(),
+        body: body.iter().skip(1).map(|segment| segment.cell).collect(),
+        apples: apples.iter().map(cell).collect(),
+        walls: walls.iter().map(cell).collect(),
+        score: score.0,
+        hz: 1. / fixed_time.timestep().as_secs_f64(),
+    };
+    remote.broadcast(&Line::State(state));
+}
+
+fn stream_end(mut remote: ResMut<RemoteControl>, mut game_end_event: EventReader<GameEnd>) {
+    if let Some(end) = game_end_event.read().next() {
+        remote.broadcast(&Line::End {
+            cause: end.cause,
+            cell: end.cell,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::score::Score;
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn remote(lockstep: bool, commands: Vec<Command>) -> RemoteControl {
+        RemoteControl {
+            listener: TcpListener::bind(("127.0.0.1", 0)).unwrap(),
+            clients: Vec::new(),
+            lockstep,
+            commands,
+            ready: false,
+            tick: 0,
+            needs_state: false,
+        }
+    }
+
+    #[test]
+    fn commands_are_parsed_from_json_lines() {
+        let mut pending = b"{\"turn\": \"up\"}\n\"straight\"\r\n\n  \n\"restart\"\n".to_vec();
+        let parsed: Vec<_> = take_lines(&mut pending)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                Command::Turn(Direction::Up),
+                Command::Straight,
+                Command::Restart
+            ]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn malformed_lines_are_errors_and_dont_stop_the_next_ones() {
+        let mut pending =
+            b"{\"turn\": \"sideways\"}\nnonsense\n{\"turn\": \"left\"\n\"straight\"\n".to_vec();
+        let parsed = take_lines(&mut pending);
+        assert_eq!(parsed.len(), 4);
+        assert!(parsed[..3].iter().all(Result::is_err));
+        assert_eq!(parsed[3], Ok(Command::Straight));
+    }
+
+    #[test]
+    fn partial_lines_wait_for_the_rest() {
+        let mut pending = b"{\"turn\": \"down\"}\n{\"tu".to_vec();
+        assert_eq!(
+            take_lines(&mut pending),
+            [Ok(Command::Turn(Direction::Down))]
+        );
+        assert_eq!(pending, b"{\"tu");
+        assert!(take_lines(&mut pending).is_empty());
+        pending.extend_from_slice(b"rn\": \"right\"}");
+        assert!(take_lines(&mut pending).is_empty());
+        pending.push(b'\n');
+        assert_eq!(
+            take_lines(&mut pending),
+            [Ok(Command::Turn(Direction::Right))]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn lockstep_moves_once_per_command() {
+        let mut world = World::new();
+        assert!(world.run_system_once(may_step).unwrap());
+        let head = world.spawn(SnakeHead::new(Vec2::X)).id();
+        world.insert_resource(remote(
+            true,
+            vec![
+                Command::Restart,
+                Command::Turn(Direction::Up),
+                Command::Straight,
+            ],
+        ));
+        let step = |world: &mut World| {
+            world.run_system_once(apply_commands).unwrap();
+            world.run_system_once(may_step).unwrap()
+        };
+        // A restart during a run isn't a move
+        assert!(!step(&mut world));
+        assert!(step(&mut world));
+        assert_eq!(world.get::<SnakeHead>(head).unwrap().direction(), Vec2::Y);
+        assert!(step(&mut world));
+        assert!(!step(&mut world));
+        assert!(!step(&mut world));
+    }
+
+    #[test]
+    fn without_lockstep_every_step_moves() {
+        let mut world = World::new();
+        let head = world.spawn(SnakeHead::new(Vec2::X)).id();
+        world.insert_resource(remote(
+            false,
+            vec![Command::Turn(Direction::Up), Command::Turn(Direction::Left)],
+        ));
+        world.run_system_once(apply_commands).unwrap();
+        assert!(world.resource::<RemoteControl>().commands.is_empty());
+        assert_eq!(
+            world.get::<SnakeHead>(head).unwrap().direction(),
+            Vec2::NEG_X
+        );
+        assert!(world.run_system_once(may_step).unwrap());
+        world.run_system_once(apply_commands).unwrap();
+        assert!(world.run_system_once(may_step).unwrap());
+    }
+
+    #[test]
+    fn only_actual_moves_advance_the_tick() {
+        let mut app = App::new();
+        app.add_event::<SnakeMoved>()
+            .insert_resource(remote(true, vec![Command::Straight]))
+            .insert_resource(GameRules::DEFAULT)
+            .init_resource::<Score>()
+            .init_resource::<Time<Fixed>>()
+            .add_systems(FixedUpdate, apply_commands)
+            .add_systems(PostUpdate, stream_state);
+        app.world_mut().spawn((
+            SnakeHead::new(Vec2::X),
+            SnakeBody::new(Entity::PLACEHOLDER, IVec2::new(3, 3)),
+        ));
+        let frame = |app: &mut App, moves: usize| {
+            // What move_player writes when the step isn't skipped
+            for _ in 0..moves {
+                app.world_mut().send_event(SnakeMoved);
+            }
+            app.world_mut().run_schedule(FixedUpdate);
+            app.update();
+            app.world().resource::<RemoteControl>().tick
+        };
+        // The command was taken, but a collision skipped the move
+        assert_eq!(frame(&mut app, 0), 0);
+        assert!(app.world().resource::<RemoteControl>().ready);
+        assert_eq!(frame(&mut app, 1), 1);
+        assert_eq!(frame(&mut app, 2), 3);
+        assert_eq!(frame(&mut app, 0), 3);
+    }
+}
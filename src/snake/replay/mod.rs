@@ -0,0 +1,575 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::body::SnakeBody;
+use super::gameplay::{Apple, Arena, EndCause, LastGameEnd, SnakeHead, SnakeStep, SpawnBoard};
+use super::rules::{GameRules, WallBehavior};
+use super::snake_resource_manager::Palette;
+
+/// Records every run, the last one is kept in the data directory so it can be exported with the
+/// replay binary.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recorder>()
+            .add_systems(
+                OnEnter(super::GameState::Gameplay),
+                start_recording.before(SpawnBoard),
+            )
+            .add_systems(
+                FixedUpdate,
+                record_moves
+                    .before(SnakeStep)
+                    .run_if(in_state(super::GameState::Gameplay)),
+            )
+            .add_systems(
+                PostUpdate,
+                (record_moves, record_apples)
+                    .chain()
+                    .run_if(in_state(super::GameState::Gameplay)),
+            )
+            .add_systems(OnExit(super::GameState::Gameplay), save_replay);
+    }
+}
+
+const LAST_REPLAY: &str = "replays/last.ron";
+
+/// A whole run as the board it started on and every move made. Apples and respawns are kept as
+/// they happened rather than drawn again from a seed, so modes with their own spawning rules play
+/// back the same.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Replay {
+    /// Replays recorded before the rules existed were all played on the default ones
+    #[serde(default)]
+    pub rules: GameRules,
+    pub obstacles: Vec<IVec2>,
+    /// Cells of the snake when the run started, from the head to the tail
+    #[serde(deserialize_with = "start_cells")]
+    pub start: Vec<IVec2>,
+    /// One unit step per move, a move wrapping around the board is still a unit step
+    pub moves: Vec<IVec2>,
+    /// Every apple, along with the number of moves made when it appeared
+    pub apples: Vec<(usize, IVec2)>,
+    /// Where the snake was put back after hitting a wall, in modes that do that
+    pub respawns: Vec<(usize, IVec2)>,
+    pub end: Option<EndCause>,
+}
+
+/// Reads the start as the cells it is now, or as the single head cell of older replays
+fn start_cells<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<IVec2>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Start {
+        Cells(Vec<IVec2>),
+        Head(IVec2),
+    }
+    Ok(match Start::deserialize(deserializer)? {
+        Start::Cells(cells) => cells,
+        Start::Head(head) => vec![head],
+    })
+}
+
+/// The board after `tick` moves
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub tick: usize,
+    /// From the head to the tail
+    pub snake: Vec<IVec2>,
+    pub apples: Vec<IVec2>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ExportOptions {
+    /// Keeps one frame out of `stride`
+    pub stride: usize,
+    /// First move shown
+    pub from: usize,
+    /// Last move shown, the end of the run when None
+    pub to: Option<usize>,
+    /// How long each frame kept stays on screen in the SVG
+    pub frame_time: Duration,
+    /// Side of one cell in the SVG, in pixels
+    pub cell_size: u32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            stride: 1,
+            from: 0,
+            to: None,
+            frame_time: Duration::from_millis(100),
+            cell_size: 16,
+        }
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Replay {
+    /// Where the game keeps the last run
+    pub fn last_path() -> PathBuf {
+        super::storage::data_path(LAST_REPLAY)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let replay: Replay = ron::from_str(&content).map_err(|error| error.to_string())?;
+        Ok(Replay {
+            rules: replay.rules.validated("of the replay"),
+            ..replay
+        })
+    }
+
+    /// Plays the moves back, one frame per move plus the starting board
+    pub fn frames(self: &Self) -> Vec<Frame> {
+        let mut snake = VecDeque::from(self.start.clone());
+        // Same as the game, each apple leaves the tail where it was for `growth` moves
+        let mut growing = 0;
+        let mut apples = Vec::new();
+        let mut pending_apples = self.apples.iter().peekable();
+        let mut respawns = self.respawns.iter().peekable();
+        let mut frames = Vec::with_capacity(self.moves.len() + 1);
+        for tick in 0..=self.moves.len() {
+            if tick > 0 {
+                let head = self.rules.next_cell(snake[0], self.moves[tick - 1]);
+                snake.push_front(head);
+                if let Some(idx) = apples.iter().position(|apple| *apple == head) {
+                    apples.swap_remove(idx);
+                    growing += self.rules.growth;
+                }
+                if growing > 0 {
+                    growing -= 1;
+                } else {
+                    snake.pop_back();
+                }
+            }
+            while let Some((_, cell)) = respawns.next_if(|(at, _)| *at <= tick) {
+                snake.iter_mut().for_each(|segment| *segment = *cell);
+            }
+            while let Some((_, cell)) = pending_apples.next_if(|(at, _)| *at <= tick) {
+                apples.push(*cell);
+            }
+            frames.push(Frame {
+                tick,
+                snake: snake.iter().copied().collect(),
+                apples: apples.clone(),
+            });
+        }
+        frames
+    }
+
+    /// Frames within the range of the options, keeping the last one so the end always shows
+    fn selected_frames(self: &Self, options: &ExportOptions) -> Vec<Frame> {
+        let to = options.to.unwrap_or(self.moves.len()).min(self.moves.len());
+        let mut frames = self.frames();
+        frames.truncate(to + 1);
+        let last = frames.pop();
+        let mut selected: Vec<Frame> = frames
+            .into_iter()
+            .skip(options.from)
+            .step_by(options.stride.max(1))
+            .collect();
+        if let Some(last) = last
+            && last.tick >= options.from
+        {
+            selected.push(last);
+        }
+        selected
+    }
+
+    fn is_wall(self: &Self, cell: IVec2) -> bool {
+        !self.rules.is_inside(cell)
+    }
+
+    /// Plain text frames, `#` for walls, `@` for the head, `o` for the body and `*` for apples
+    pub fn to_ascii(self: &Self, options: &ExportOptions) -> String {
+        let mut text = String::new();
+        for frame in self.selected_frames(options) {
+            let _ = writeln!(text, "Move {}", frame.tick);
+            for y in (-1..=self.rules.side + 1).rev() {
+                for x in -1..=self.rules.side + 1 {
+                    let cell = IVec2::new(x, y);
+                    let char = if frame.snake.first() == Some(&cell) {
+                        '@'
+                    } else if frame.snake.contains(&cell) {
+                        'o'
+                    } else if frame.apples.contains(&cell) {
+                        '*'
+                    } else if self.is_wall(cell) || self.obstacles.contains(&cell) {
+                        '#'
+                    } else {
+                        '.'
+                    };
+                    text.push(char);
+                }
+                text.push('\n');
+            }
+            text.push('\n');
+        }
+        if let Some(end) = self.end {
+            let _ = writeln!(text, "Ended by {:?}", end);
+        }
+        text
+    }
+
+    /// An SVG looping over the frames, colored with the palette of a theme
+    pub fn to_svg(self: &Self, palette: &Palette, options: &ExportOptions) -> String {
+        let size = options.cell_size as i32;
+        let side = (self.rules.side + 3) * size;
+        let rect = |cell: IVec2| {
+            format!(
+                r#"x="{}" y="{}" width="{size}" height="{size}""#,
+                (cell.x + 1) * size,
+                (self.rules.side + 1 - cell.y) * size
+            )
+        };
+        let hex = |color: Color| Srgba::from(color).to_hex();
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{side}" height="{side}" viewBox="0 0 {side} {side}">"#
+        );
+        svg.push('\n');
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{side}" height="{side}" fill="{}"/>"#,
+            hex(palette.wall)
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{size}" y="{size}" width="{0}" height="{0}" fill="{1}"/>"#,
+            (self.rules.side + 1) * size,
+            hex(palette.background)
+        );
+        for obstacle in &self.obstacles {
+            let _ = writeln!(
+                svg,
+                r#"<rect {} fill="{}"/>"#,
+                rect(*obstacle),
+                hex(palette.wall)
+            );
+        }
+
+        let frames = self.selected_frames(options);
+        let count = frames.len();
+        let total = options.frame_time.as_secs_f64() * count as f64;
+        for (idx, frame) in frames.iter().enumerate() {
+            if count > 1 {
+                // Visible during its own slice of the loop only
+                let _ = writeln!(
+                    svg,
+                    r#"<g visibility="hidden"><animate attributeName="visibility" calcMode="discrete" values="hidden;visible;hidden" keyTimes="0;{:.6};{:.6}" dur="{total:.3}s" repeatCount="indefinite"/>"#,
+                    idx as f64 / count as f64,
+                    (idx + 1) as f64 / count as f64
+                );
+            } else {
+                svg.push_str("<g>\n");
+            }
+            for apple in &frame.apples {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect {} rx="{}" fill="{}"/>"#,
+                    rect(*apple),
+                    size / 2,
+                    hex(palette.apple)
+                );
+            }
+            // From the tail, so the head is drawn on top of a stacked body
+            let length = frame.snake.len();
+            for (segment_idx, cell) in frame.snake.iter().enumerate().rev() {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect {} rx="{}" fill="{}"/>"#,
+                    rect(*cell),
+                    size / 4,
+                    hex(palette.segment(segment_idx, length))
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// The run being recorded
+#[derive(Resource, Default)]
+struct Recorder {
+    replay: Replay,
+    started: bool,
+    /// Head cell at the last look
+    last: IVec2,
+}
+
+fn start_recording(mut recorder: ResMut<Recorder>, arena: Res<Arena>, rules: Res<GameRules>) {
+    *recorder = Recorder {
+        replay: Replay {
+            rules: *rules,
+            obstacles: arena.obstacles.clone(),
+            ..default()
+        },
+        ..default()
+    };
+}
+
+/// Runs before every move and once per frame, so each move is seen on its own
+fn record_moves(mut recorder: ResMut<Recorder>, body: Single<&SnakeBody, With<SnakeHead>>) {
+    let cell = body.head().cell;
+    if !recorder.started {
+        recorder.started = true;
+        recorder.replay.start = body.iter().map(|segment| segment.cell).collect();
+        recorder.last = cell;
+        return;
+    }
+    let mut step = cell - recorder.last;
+    if step == IVec2::ZERO {
+        return;
+    }
+    // Crossing the whole board in one move is wrapping around it
+    if recorder.replay.rules.walls == WallBehavior::Wrap {
+        let side = recorder.replay.rules.side;
+        for axis in [&mut step.x, &mut step.y] {
+            if axis.abs() == side {
+                *axis = -axis.signum();
+            }
+        }
+    }
+    recorder.last = cell;
+    // A respawn stacks the whole body on the head, a move never does
+    let stacked = body.len() > 1 && body.iter().all(|segment| segment.cell == cell);
+    if step.abs().element_sum() == 1 && !stacked {
+        recorder.replay.moves.push(step);
+    } else {
+        let tick = recorder.replay.moves.len();
+        recorder.replay.respawns.push((tick, cell));
+    }
+}
+
+fn record_apples(mut recorder: ResMut<Recorder>, apples: Query<&Transform, Added<Apple>>) {
+    let tick = recorder.replay.moves.len();
+    let rules = recorder.replay.rules;
+    for transform in &apples {
+        let cell = rules.board_cell(transform.translation.truncate());
+        recorder.replay.apples.push((tick, cell));
+    }
+}
+
+fn save_replay(mut recorder: ResMut<Recorder>, last_game_end: Res<LastGameEnd>) {
+    if !recorder.started {
+        return;
+    }
+    recorder.replay.end = last_game_end.0.map(|end| end.cause);
+    super::storage::save_ron(LAST_REPLAY, &recorder.replay);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::theme::Theme;
+    use super::*;
+
+    fn straight(start: Vec<IVec2>, moves: usize) -> Replay {
+        Replay {
+            start,
+            moves: vec![IVec2::X; moves],
+            ..default()
+        }
+    }
+
+    fn snakes(replay: &Replay) -> Vec<Vec<IVec2>> {
+        replay
+            .frames()
+            .into_iter()
+            .map(|frame| frame.snake)
+            .collect()
+    }
+
+    fn ticks(frames: &[Frame]) -> Vec<usize> {
+        frames.iter().map(|frame| frame.tick).collect()
+    }
+
+    #[test]
+    fn eating_grows_over_the_next_moves() {
+        let replay = Replay {
+            rules: GameRules {
+                growth: 2,
+                ..GameRules::DEFAULT
+            },
+            apples: vec![(0, IVec2::new(3, 2)), (1, IVec2::new(6, 6))],
+            ..straight(vec![IVec2::new(2, 2), IVec2::new(1, 2)], 4)
+        };
+        let frames = replay.frames();
+        assert_eq!(ticks(&frames), [0, 1, 2, 3, 4]);
+        let lengths: Vec<usize> = frames.iter().map(|frame| frame.snake.len()).collect();
+        assert_eq!(lengths, [2, 3, 4, 4, 4]);
+        assert_eq!(
+            frames[2].snake,
+            [
+                IVec2::new(4, 2),
+                IVec2::new(3, 2),
+                IVec2::new(2, 2),
+                IVec2::new(1, 2)
+            ]
+        );
+        // The first apple is eaten on the move the second one shows up
+        assert_eq!(frames[0].apples, [IVec2::new(3, 2)]);
+        assert_eq!(frames[1].apples, [IVec2::new(6, 6)]);
+        assert_eq!(frames[4].apples, [IVec2::new(6, 6)]);
+    }
+
+    #[test]
+    fn moves_wrap_around_the_board() {
+        let replay = Replay {
+            rules: GameRules {
+                side: 4,
+                walls: WallBehavior::Wrap,
+                ..GameRules::DEFAULT
+            },
+            start: vec![IVec2::new(4, 0)],
+            moves: vec![IVec2::X, IVec2::NEG_Y, IVec2::NEG_Y],
+            ..default()
+        };
+        let heads: Vec<IVec2> = snakes(&replay).iter().map(|snake| snake[0]).collect();
+        assert_eq!(
+            heads,
+            [
+                IVec2::new(4, 0),
+                IVec2::new(0, 0),
+                IVec2::new(0, 4),
+                IVec2::new(0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn respawn_stacks_the_body_which_then_unfolds() {
+        let replay = Replay {
+            respawns: vec![(1, IVec2::new(10, 10))],
+            moves: vec![IVec2::X, IVec2::Y, IVec2::Y],
+            ..straight(
+                vec![IVec2::new(2, 2), IVec2::new(1, 2), IVec2::new(0, 2)],
+                0,
+            )
+        };
+        let snakes = snakes(&replay);
+        assert_eq!(snakes[1], [IVec2::new(10, 10); 3]);
+        assert_eq!(
+            snakes[2],
+            [IVec2::new(10, 11), IVec2::new(10, 10), IVec2::new(10, 10)]
+        );
+        assert_eq!(
+            snakes[3],
+            [IVec2::new(10, 12), IVec2::new(10, 11), IVec2::new(10, 10)]
+        );
+    }
+
+    #[test]
+    fn trimming_keeps_the_last_frame() {
+        let replay = straight(vec![IVec2::new(0, 5)], 10);
+        let select = |from: usize, to: Option<usize>, stride: usize| {
+            ticks(&replay.selected_frames(&ExportOptions {
+                from,
+                to,
+                stride,
+                ..default()
+            }))
+        };
+        assert_eq!(select(0, None, 1), (0..=10).collect::<Vec<_>>());
+        assert_eq!(select(0, None, 4), [0, 4, 8, 10]);
+        assert_eq!(select(2, Some(7), 3), [2, 5, 7]);
+        assert_eq!(select(0, Some(40), 5), [0, 5, 10]);
+        assert_eq!(select(10, None, 3), [10]);
+        assert!(select(11, None, 1).is_empty());
+        // A stride of 0 is taken as 1
+        assert_eq!(select(8, None, 0), [8, 9, 10]);
+    }
+
+    #[test]
+    fn ascii_draws_each_frame() {
+        let replay = Replay {
+            rules: GameRules {
+                side: 2,
+                ..GameRules::DEFAULT
+            },
+            obstacles: vec![IVec2::new(2, 0)],
+            apples: vec![(0, IVec2::new(2, 2))],
+            end: Some(EndCause::Wall),
+            ..straight(vec![IVec2::new(1, 1), IVec2::new(0, 1)], 1)
+        };
+        let text = replay.to_ascii(&ExportOptions::default());
+        let expected = "\
+Move 0
+#####
+#..*#
+#o@.#
+#..##
+#####
+
+Move 1
+#####
+#..*#
+#.o@#
+#..##
+#####
+
+Ended by Wall
+";
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn svg_shows_each_frame_in_its_own_slice_of_the_loop() {
+        let replay = straight(vec![IVec2::new(0, 5)], 2);
+        let palette = Palette::new(&Theme::default());
+        let svg = replay.to_svg(&palette, &ExportOptions::default());
+        let key_times: Vec<&str> = svg
+            .match_indices("keyTimes=\"")
+            .map(|(idx, found)| {
+                let rest = &svg[idx + found.len()..];
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect();
+        assert_eq!(
+            key_times,
+            [
+                "0;0.000000;0.333333",
+                "0;0.333333;0.666667",
+                "0;0.666667;1.000000"
+            ]
+        );
+        assert_eq!(svg.matches(r#"dur="0.300s""#).count(), 3);
+        assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+
+        // A single frame stays on screen
+        let svg = replay.to_svg(
+            &palette,
+            &ExportOptions {
+                from: 2,
+                ..default()
+            },
+        );
+        assert!(svg.contains("<g>\n") && !svg.contains("<animate"));
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn older_replays_still_load() {
+        let current = Replay {
+            start: vec![IVec2::new(3, 4), IVec2::new(2, 4)],
+            ..straight(vec![], 2)
+        };
+        let text = ron::to_string(&current).unwrap();
+        assert_eq!(ron::from_str::<Replay>(&text).unwrap().start, current.start);
+
+        // Before the rules and the starting length, with a seed that is no longer kept
+        let old = "(seed: 5, side: 30, obstacles: [], start: (3, 4), moves: [(1, 0)], \
+                   apples: [(0, (5, 4))], respawns: [], end: Some(Wall))";
+        let replay = ron::from_str::<Replay>(old).unwrap();
+        assert_eq!(replay.start, [IVec2::new(3, 4)]);
+        assert_eq!(replay.rules, GameRules::DEFAULT);
+        assert_eq!(replay.frames()[1].snake, [IVec2::new(4, 4)]);
+    }
+}
@@ -14,12 +14,18 @@ use std::sync::Mutex;
 #[derive(Resource)]
 pub struct RngResource(Mutex<StdRng>);
 
+#[allow(clippy::needless_arbitrary_self_type)]
 impl RngResource {
     pub fn random_in_range(self: &Self, range: Range<u64>) -> u64 {
         self.0.lock().unwrap().gen_range(range)
     }
 
-    pub fn random(self: &Self) -> u64 {
+    /// Same seed, same draws. Used where peers have to agree on every random outcome.
+    pub fn seeded(seed: u64) -> Self {
+        RngResource(Mutex::new(StdRng::seed_from_u64(seed)))
+    }
+
+    pub fn random_seed(self: &Self) -> u64 {
         self.0.lock().unwrap().r#gen()
     }
 }
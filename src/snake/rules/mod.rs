@@ -0,0 +1,306 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RulesPresets::load())
+            .init_resource::<GameRules>();
+    }
+}
+
+const RULES_FILE: &str = "assets/rules.ron";
+/// Keeps the grids, which hold every cell of the board, to a sane size
+const MAX_SIDE: i32 = 200;
+/// How far the board sits from the camera for each cell of its side, border included, so the
+/// default camera always fits it.
+const DEPTH_PER_CELL: f32 = 50. / 33.;
+
+/// Everything a run plays by, set by whoever starts it from the preset picked in the settings.
+/// Fields left out of a preset keep their default.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct GameRules {
+    /// Cells go from 0 to `side` on both axes, both ends included
+    pub side: i32,
+    pub walls: WallBehavior,
+    /// Apples on the board at once
+    pub apples: usize,
+    /// Segments the snake grows by for each apple
+    pub growth: usize,
+    /// Segments of the snake when a run starts, laid out behind the head
+    pub start_length: usize,
+    pub speed: SpeedCurve,
+    /// Whether biting its own body ends the run, the snake goes through it otherwise
+    pub self_collision: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallBehavior {
+    /// Running into the border ends the run, or costs time in the modes that respawn
+    #[default]
+    Solid,
+    /// The snake leaves through one side of the board and comes back through the opposite one
+    Wrap,
+}
+
+/// Moves per second, `start_hz + per_doubling * log2(length)` up to `max_hz`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SpeedCurve {
+    pub start_hz: f64,
+    pub max_hz: f64,
+    pub per_doubling: f64,
+}
+
+impl Default for SpeedCurve {
+    fn default() -> Self {
+        GameRules::DEFAULT.speed
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl GameRules {
+    /// The rules the game always had, the campaign levels and versus are laid out for them
+    pub const DEFAULT: Self = Self {
+        side: 30,
+        walls: WallBehavior::Solid,
+        apples: 1,
+        growth: 1,
+        start_length: 1,
+        speed: SpeedCurve {
+            start_hz: 10.,
+            max_hz: 30.,
+            per_doubling: 2.,
+        },
+        self_collision: true,
+    };
+
+    /// The same rules with whatever would break the game brought back in range, warning about
+    /// each change. `name` tells the rules apart in the warnings.
+    pub fn validated(mut self: Self, name: &str) -> Self {
+        if !(1..=MAX_SIDE).contains(&self.side) {
+            let side = self.side.clamp(1, MAX_SIDE);
+            warn!(
+                "Rules {}: side {} is out of range, using {}",
+                name, self.side, side
+            );
+            self.side = side;
+        }
+        let clamp = |field: &str, value: usize, max: usize| {
+            let clamped = value.clamp(1, max);
+            if clamped != value {
+                warn!(
+                    "Rules {}: {} {} is out of range, using {}",
+                    name, field, value, clamped
+                );
+            }
+            clamped
+        };
+        // A snake filling the board would win as it spawns, the apples need the cells it leaves
+        // free, and growing past the board only spawns segments with nowhere to go
+        let cells = self.board_cells();
+        self.start_length = clamp("start_length", self.start_length, cells - 1);
+        self.apples = clamp("apples", self.apples, cells - self.start_length);
+        self.growth = clamp("growth", self.growth, cells);
+        let speed = &mut self.speed;
+        if !speed.start_hz.is_finite() || speed.start_hz <= 0. {
+            let start_hz = Self::DEFAULT.speed.start_hz;
+            warn!(
+                "Rules {}: start_hz {} isn't a positive number, using {}",
+                name, speed.start_hz, start_hz
+            );
+            speed.start_hz = start_hz;
+        }
+        if !speed.max_hz.is_finite() || speed.max_hz < speed.start_hz {
+            warn!(
+                "Rules {}: max_hz {} is out of range, using {}",
+                name, speed.max_hz, speed.start_hz
+            );
+            speed.max_hz = speed.start_hz;
+        }
+        if !speed.per_doubling.is_finite() || speed.per_doubling < 0. {
+            warn!(
+                "Rules {}: per_doubling {} is negative or not finite, using 0",
+                name, speed.per_doubling
+            );
+            speed.per_doubling = 0.;
+        }
+        self
+    }
+
+    /// Playable cells, the border walls aside
+    pub fn board_cells(self: &Self) -> usize {
+        (self.side as usize + 1) * (self.side as usize + 1)
+    }
+
+    pub fn is_inside(self: &Self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmple(IVec2::splat(self.side)).all()
+    }
+
+    /// Where a move from `cell` lands, wrapping around the board when the walls let it
+    pub fn next_cell(self: &Self, cell: IVec2, direction: IVec2) -> IVec2 {
+        let next = cell + direction;
+        match self.walls {
+            WallBehavior::Solid => next,
+            WallBehavior::Wrap => next.rem_euclid(IVec2::splat(self.side + 1)),
+        }
+    }
+
+    /// Distance of the board in front of the camera
+    pub fn depth(self: &Self) -> f32 {
+        -((self.side + 3) as f32) * DEPTH_PER_CELL
+    }
+
+    pub fn cell_position(self: &Self, cell: IVec2) -> Vec3 {
+        let half_side = self.side as f32 / 2.;
+        Vec3::new(
+            cell.x as f32 - half_side,
+            cell.y as f32 - half_side,
+            self.depth(),
+        )
+    }
+
+    /// Inverse of cell_position, border walls are at -1 and `side + 1`
+    pub fn board_cell(self: &Self, position: Vec2) -> IVec2 {
+        (position + self.side as f32 / 2.).round().as_ivec2()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Preset {
+    name: String,
+    #[serde(default)]
+    rules: GameRules,
+}
+
+/// Every preset of assets/rules.ron, in the order of the file.
+#[derive(Resource)]
+pub struct RulesPresets(Vec<Preset>);
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl RulesPresets {
+    pub fn load() -> Self {
+        let path = FileAssetReader::get_base_path().join(RULES_FILE);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| Self::parse(&content));
+        let presets = match parsed {
+            Ok(presets) => presets,
+            Err(error) => {
+                warn!("Failed to load {}: {}", path.display(), error);
+                Vec::new()
+            }
+        };
+        if presets.is_empty() {
+            return Self(vec![Preset {
+                name: "Classic".to_owned(),
+                rules: GameRules::DEFAULT,
+            }]);
+        }
+        Self(presets)
+    }
+
+    fn parse(content: &str) -> Result<Vec<Preset>, String> {
+        let presets = ron::from_str::<Vec<Preset>>(content).map_err(|e| e.to_string())?;
+        Ok(presets
+            .into_iter()
+            .map(|preset| Preset {
+                rules: preset.rules.validated(&preset.name),
+                name: preset.name,
+            })
+            .collect())
+    }
+
+    /// Falls back to the first preset when the name is unknown
+    pub fn get(self: &Self, name: &str) -> GameRules {
+        self.0
+            .iter()
+            .find(|preset| preset.name == name)
+            .unwrap_or(&self.0[0])
+            .rules
+    }
+
+    pub fn names(self: &Self) -> Vec<String> {
+        self.0.iter().map(|preset| preset.name.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES_ASSET: &str = include_str!("../../../assets/rules.ron");
+
+    #[test]
+    fn shipped_presets_parse_and_are_valid() {
+        let raw = ron::from_str::<Vec<Preset>>(RULES_ASSET).unwrap();
+        let presets = RulesPresets(RulesPresets::parse(RULES_ASSET).unwrap());
+        assert!(presets.names().len() > 1);
+        assert_eq!(presets.names()[0], "Classic");
+        assert_eq!(presets.get("Classic"), GameRules::DEFAULT);
+        for preset in raw {
+            assert_eq!(presets.get(&preset.name), preset.rules, "{}", preset.name);
+        }
+        // Unknown names get the first preset
+        assert_eq!(presets.get("Nope"), GameRules::DEFAULT);
+    }
+
+    #[test]
+    fn broken_values_are_brought_back_in_range() {
+        let presets = RulesPresets::parse(
+            "[(name: \"Broken\", rules: (side: 0, apples: 0, growth: 0, start_length: 0, \
+             speed: (start_hz: -1., max_hz: 2., per_doubling: -3.)))]",
+        )
+        .unwrap();
+        let rules = presets[0].rules;
+        assert_eq!(rules.side, 1);
+        assert_eq!((rules.apples, rules.growth, rules.start_length), (1, 1, 1));
+        assert_eq!(rules.speed.start_hz, GameRules::DEFAULT.speed.start_hz);
+        assert_eq!(rules.speed.max_hz, rules.speed.start_hz);
+        assert_eq!(rules.speed.per_doubling, 0.);
+        // Usable on the smallest board
+        assert_eq!(rules.board_cells(), 4);
+        assert_eq!(
+            rules.next_cell(IVec2::new(1, 1), IVec2::X),
+            IVec2::new(2, 1)
+        );
+
+        let big = GameRules {
+            side: 100_000,
+            ..GameRules::DEFAULT
+        };
+        assert_eq!(big.validated("Big").side, MAX_SIDE);
+        assert_eq!(GameRules::DEFAULT.validated("Default"), GameRules::DEFAULT);
+
+        // Nothing can outgrow the board
+        let huge = GameRules {
+            side: 3,
+            apples: 1_000,
+            growth: usize::MAX,
+            start_length: 16,
+            ..GameRules::DEFAULT
+        }
+        .validated("Huge");
+        assert_eq!(huge.start_length, 15);
+        assert_eq!(huge.apples, 1);
+        assert_eq!(huge.growth, 16);
+        let crowded = GameRules {
+            side: 3,
+            apples: 1_000,
+            start_length: 4,
+            ..GameRules::DEFAULT
+        }
+        .validated("Crowded");
+        assert_eq!(crowded.apples, 12);
+        assert_eq!(crowded.start_length, 4);
+    }
+}
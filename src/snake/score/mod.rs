@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::GameMode;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            .init_resource::<Score>()
+            .init_resource::<RunTime>()
+            .add_systems(OnEnter(super::GameState::Gameplay), reset_run)
+            .add_systems(
+                Update,
+                (tick_run_time, add_points)
+                    .run_if(in_state(super::GameState::Gameplay))
+                    .run_if(in_state(super::GameplayState::Running)),
+            )
+            .add_systems(OnExit(super::GameState::Gameplay), save_high_score);
+    }
+}
+
+const HIGH_SCORES_FILE: &str = "high_scores.ron";
+pub const POINTS_PER_APPLE: u32 = 10;
+
+#[derive(Resource, Default)]
+pub struct Score(pub u32);
+
+/// Time spent in the current run, pauses don't count.
+#[derive(Resource, Default)]
+pub struct RunTime(pub Duration);
+
+/// Best scores are kept apart per mode, as their scores aren't comparable.
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HighScores {
+    pub best: u32,
+    pub time_attack_best: u32,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl HighScores {
+    /// Campaign levels are judged by their goals, not by score
+    pub fn best(self: &Self, mode: GameMode) -> Option<u32> {
+        match mode {
+            GameMode::Classic => Some(self.best),
+            GameMode::TimeAttack => Some(self.time_attack_best),
+            GameMode::Campaign => None,
+        }
+    }
+
+    fn best_mut(self: &mut Self, mode: GameMode) -> Option<&mut u32> {
+        match mode {
+            GameMode::Classic => Some(&mut self.best),
+            GameMode::TimeAttack => Some(&mut self.time_attack_best),
+            GameMode::Campaign => None,
+        }
+    }
+
+    fn load() -> Self {
+        super::storage::load_ron(HIGH_SCORES_FILE).unwrap_or_default()
+    }
+
+    fn save(self: &Self) {
+        super::storage::save_ron(HIGH_SCORES_FILE, self);
+    }
+}
+
+fn reset_run(mut score: ResMut<Score>, mut run_time: ResMut<RunTime>) {
+    score.0 = 0;
+    run_time.0 = Duration::ZERO;
+}
+
+fn tick_run_time(time: Res<Time>, mut run_time: ResMut<RunTime>) {
+    run_time.0 += time.delta();
+}
+
+fn add_points(
+    mut apple_eaten_event: EventReader<super::AppleEaten>,
+    mut score: ResMut<Score>,
+    mut high_scores: ResMut<HighScores>,
+    mode: Res<GameMode>,
+) {
+    for _ in apple_eaten_event.read() {
+        score.0 += POINTS_PER_APPLE;
+    }
+    if let Some(best) = high_scores.best_mut(*mode)
+        && score.0 > *best
+    {
+        *best = score.0;
+    }
+}
+
+fn save_high_score(high_scores: Res<HighScores>) {
+    high_scores.save();
+}
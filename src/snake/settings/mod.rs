@@ -0,0 +1,471 @@
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use bevy::window::{
+    MonitorSelection, PresentMode, PrimaryWindow, VideoModeSelection, WindowMode, WindowResolution,
+};
+use serde::{Deserialize, Serialize};
+
+use super::localization::{Language, Localization};
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen};
+use super::rules::RulesPresets;
+use super::theme::Themes;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        // The settings are usually loaded by main before the window is created, this is only a
+        // fallback for when the plugin is added on its own.
+        if !app.world().contains_resource::<Settings>() {
+            app.insert_resource(Settings::load());
+        }
+        app.add_menu(MenuId::Settings, settings_menu).add_systems(
+            Update,
+            (apply_settings, apply_channel_volumes, save_settings),
+        );
+    }
+}
+
+const SETTINGS_FILE: &str = "settings.ron";
+/// Bump whenever a field changes meaning, files from other versions are discarded.
+const SETTINGS_VERSION: u32 = 1;
+const VOLUME_STEP: f32 = 0.1;
+
+/// Saved resolutions outside of these are brought back within them
+const MIN_RESOLUTION: (u32, u32) = (640, 360);
+const MAX_RESOLUTION: (u32, u32) = (7680, 4320);
+
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl WindowModeSetting {
+    const ALL: [WindowModeSetting; 3] = [
+        WindowModeSetting::Windowed,
+        WindowModeSetting::Borderless,
+        WindowModeSetting::Fullscreen,
+    ];
+
+    fn key(self: &Self) -> &'static str {
+        match self {
+            WindowModeSetting::Windowed => "settings.window_mode.windowed",
+            WindowModeSetting::Borderless => "settings.window_mode.borderless",
+            WindowModeSetting::Fullscreen => "settings.window_mode.fullscreen",
+        }
+    }
+
+    fn window_mode(self: &Self) -> WindowMode {
+        match self {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => {
+                WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+            }
+            WindowModeSetting::Fullscreen => {
+                WindowMode::Fullscreen(MonitorSelection::Current, VideoModeSelection::Current)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Difficulty {
+    const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    fn key(self: &Self) -> &'static str {
+        match self {
+            Difficulty::Easy => "difficulty.easy",
+            Difficulty::Normal => "difficulty.normal",
+            Difficulty::Hard => "difficulty.hard",
+        }
+    }
+
+    /// Scales the snake pace, both the starting and the maximum one.
+    pub fn speed_multiplier(self: &Self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.5,
+        }
+    }
+}
+
+/// Which volume slider a sound follows on top of the master one, sounds without it only follow
+/// the master volume.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioChannel {
+    Music,
+    Effects,
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub window_mode: WindowModeSetting,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub difficulty: Difficulty,
+    pub language: Language,
+    pub theme: String,
+    /// Name of the rules preset runs start with
+    pub rules: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            window_mode: WindowModeSetting::default(),
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            master_volume: 1.,
+            music_volume: 0.8,
+            effects_volume: 0.8,
+            difficulty: Difficulty::default(),
+            language: Language::default(),
+            theme: "Classic".to_owned(),
+            rules: "Classic".to_owned(),
+        }
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Settings {
+    pub fn load() -> Self {
+        match super::storage::load_ron::<Settings>(SETTINGS_FILE) {
+            Some(settings) if settings.version == SETTINGS_VERSION => settings.validated(),
+            Some(settings) => {
+                warn!(
+                    "Discarding settings from version {}, expected {}",
+                    settings.version, SETTINGS_VERSION
+                );
+                Settings::default()
+            }
+            None => Settings::default(),
+        }
+    }
+
+    /// The same settings with the values a hand edited file could break brought back in range,
+    /// warning about each change
+    fn validated(mut self: Self) -> Self {
+        let defaults = Settings::default();
+        for (field, value, default) in [
+            (
+                "master_volume",
+                &mut self.master_volume,
+                defaults.master_volume,
+            ),
+            (
+                "music_volume",
+                &mut self.music_volume,
+                defaults.music_volume,
+            ),
+            (
+                "effects_volume",
+                &mut self.effects_volume,
+                defaults.effects_volume,
+            ),
+        ] {
+            let volume = if value.is_nan() {
+                default
+            } else {
+                value.clamp(0., 1.)
+            };
+            if volume != *value {
+                warn!(
+                    "Settings: {} {} is out of range, using {}",
+                    field, value, volume
+                );
+                *value = volume;
+            }
+        }
+        let (width, height) = self.resolution;
+        let resolution = (
+            width.clamp(MIN_RESOLUTION.0, MAX_RESOLUTION.0),
+            height.clamp(MIN_RESOLUTION.1, MAX_RESOLUTION.1),
+        );
+        if resolution != self.resolution {
+            warn!(
+                "Settings: resolution {}x{} is out of range, using {}x{}",
+                width, height, resolution.0, resolution.1
+            );
+            self.resolution = resolution;
+        }
+        self
+    }
+
+    pub fn save(self: &Self) {
+        super::storage::save_ron(SETTINGS_FILE, self);
+    }
+
+    /// Builds the primary window, so the game already starts with the saved configuration.
+    pub fn window(self: &Self) -> Window {
+        Window {
+            title: "Snake".to_owned(),
+            mode: self.window_mode.window_mode(),
+            resolution: WindowResolution::new(self.resolution.0 as f32, self.resolution.1 as f32),
+            present_mode: self.present_mode(),
+            ..default()
+        }
+    }
+
+    /// Linear volume of the sounds of a channel, the master volume included
+    pub fn channel_volume(self: &Self, channel: AudioChannel) -> f32 {
+        let volume = match channel {
+            AudioChannel::Music => self.music_volume,
+            AudioChannel::Effects => self.effects_volume,
+        };
+        self.master_volume * volume
+    }
+
+    fn present_mode(self: &Self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut global_volume: ResMut<GlobalVolume>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let mode = settings.window_mode.window_mode();
+    if window.mode != mode {
+        window.mode = mode;
+    }
+    let (width, height) = settings.resolution;
+    if window.resolution.width() != width as f32 || window.resolution.height() != height as f32 {
+        window.resolution.set(width as f32, height as f32);
+    }
+    let present_mode = settings.present_mode();
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+    global_volume.volume = Volume::Linear(settings.master_volume);
+}
+
+/// Sets the volume of every sound of a channel as soon as it starts playing and whenever the
+/// settings change, replacing the volume it was started with.
+fn apply_channel_volumes(
+    settings: Res<Settings>,
+    mut sinks: Query<(&AudioChannel, &mut AudioSink)>,
+) {
+    for (channel, mut sink) in &mut sinks {
+        if settings.is_changed() || sink.is_added() {
+            sink.set_volume(Volume::Linear(settings.channel_volume(*channel)));
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
+
+fn settings_menu(_world: &World) -> MenuScreen {
+    MenuScreen {
+        title: Some("menu.settings"),
+        items: vec![
+            MenuItem::Choice {
+                label: "settings.window_mode",
+                options: |world| {
+                    let localization = world.resource::<Localization>();
+                    WindowModeSetting::ALL
+                        .iter()
+                        .map(|mode| localization.get(mode.key()))
+                        .collect()
+                },
+                get: |world| {
+                    let current = world.resource::<Settings>().window_mode;
+                    WindowModeSetting::ALL
+                        .iter()
+                        .position(|mode| *mode == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| {
+                    world.resource_mut::<Settings>().window_mode = WindowModeSetting::ALL[idx]
+                },
+            },
+            MenuItem::Choice {
+                label: "settings.resolution",
+                options: |_| {
+                    RESOLUTIONS
+                        .iter()
+                        .map(|(width, height)| format!("{width}x{height}"))
+                        .collect()
+                },
+                get: |world| {
+                    let current = world.resource::<Settings>().resolution;
+                    RESOLUTIONS
+                        .iter()
+                        .position(|resolution| *resolution == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| world.resource_mut::<Settings>().resolution = RESOLUTIONS[idx],
+            },
+            MenuItem::Toggle {
+                label: "settings.vsync",
+                get: |world| world.resource::<Settings>().vsync,
+                set: |world, value| world.resource_mut::<Settings>().vsync = value,
+            },
+            MenuItem::Slider {
+                label: "settings.master_volume",
+                get: |world| world.resource::<Settings>().master_volume,
+                set: |world, value| world.resource_mut::<Settings>().master_volume = value,
+                min: 0.,
+                max: 1.,
+                step: VOLUME_STEP,
+            },
+            MenuItem::Slider {
+                label: "settings.music_volume",
+                get: |world| world.resource::<Settings>().music_volume,
+                set: |world, value| world.resource_mut::<Settings>().music_volume = value,
+                min: 0.,
+                max: 1.,
+                step: VOLUME_STEP,
+            },
+            MenuItem::Slider {
+                label: "settings.effects_volume",
+                get: |world| world.resource::<Settings>().effects_volume,
+                set: |world, value| world.resource_mut::<Settings>().effects_volume = value,
+                min: 0.,
+                max: 1.,
+                step: VOLUME_STEP,
+            },
+            MenuItem::Choice {
+                label: "settings.difficulty",
+                options: |world| {
+                    let localization = world.resource::<Localization>();
+                    Difficulty::ALL
+                        .iter()
+                        .map(|difficulty| localization.get(difficulty.key()))
+                        .collect()
+                },
+                get: |world| {
+                    let current = world.resource::<Settings>().difficulty;
+                    Difficulty::ALL
+                        .iter()
+                        .position(|difficulty| *difficulty == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| {
+                    world.resource_mut::<Settings>().difficulty = Difficulty::ALL[idx]
+                },
+            },
+            MenuItem::Choice {
+                label: "settings.language",
+                options: |world| {
+                    let localization = world.resource::<Localization>();
+                    Language::ALL
+                        .iter()
+                        .map(|language| localization.get(&format!("language.{}", language.code())))
+                        .collect()
+                },
+                get: |world| {
+                    let current = world.resource::<Settings>().language;
+                    Language::ALL
+                        .iter()
+                        .position(|language| *language == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| world.resource_mut::<Settings>().language = Language::ALL[idx],
+            },
+            MenuItem::Choice {
+                label: "settings.theme",
+                options: |world| world.resource::<Themes>().names(),
+                get: |world| {
+                    let current = &world.resource::<Settings>().theme;
+                    world
+                        .resource::<Themes>()
+                        .names()
+                        .iter()
+                        .position(|name| name == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| {
+                    let name = world.resource::<Themes>().names()[idx].clone();
+                    world.resource_mut::<Settings>().theme = name;
+                },
+            },
+            MenuItem::Choice {
+                label: "settings.rules",
+                options: |world| world.resource::<RulesPresets>().names(),
+                get: |world| {
+                    let current = &world.resource::<Settings>().rules;
+                    world
+                        .resource::<RulesPresets>()
+                        .names()
+                        .iter()
+                        .position(|name| name == current)
+                        .unwrap_or(0)
+                },
+                set: |world, idx| {
+                    let name = world.resource::<RulesPresets>().names()[idx].clone();
+                    world.resource_mut::<Settings>().rules = name;
+                },
+            },
+            MenuItem::Button {
+                label: "common.back",
+                action: MenuAction::Back,
+            },
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_values_are_brought_back_in_range() {
+        let settings = Settings {
+            master_volume: 3.,
+            music_volume: -0.5,
+            effects_volume: f32::NAN,
+            resolution: (0, 100_000),
+            ..default()
+        }
+        .validated();
+        assert_eq!(settings.master_volume, 1.);
+        assert_eq!(settings.music_volume, 0.);
+        assert_eq!(settings.effects_volume, Settings::default().effects_volume);
+        assert_eq!(settings.resolution, (MIN_RESOLUTION.0, MAX_RESOLUTION.1));
+    }
+
+    #[test]
+    fn defaults_and_listed_resolutions_are_valid() {
+        assert_eq!(Settings::default().validated(), Settings::default());
+        for resolution in RESOLUTIONS {
+            let settings = Settings {
+                resolution,
+                ..default()
+            };
+            assert_eq!(settings.clone().validated(), settings);
+        }
+    }
+}
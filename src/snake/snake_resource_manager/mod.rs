@@ -1,17 +1,33 @@
 use bevy::prelude::*;
 
+use super::loading::LoadedModels;
+use super::theme::{SegmentPattern, Theme, Themes};
+
 /// The meshes and materials will always be in memory, but it is not an issue given that
 /// A) They don't take much memory
 /// B) It is only a snake game that will only be about snake
 #[derive(Resource)]
 pub struct SnakeResourceManager {
+    theme: Theme,
+    palette: Palette,
     apple_mesh: Handle<Mesh>,
     apple_materials: Vec<Handle<StandardMaterial>>,
     ball_mesh: Handle<Mesh>,
+    head_mesh: Handle<Mesh>,
     ball_materials: Vec<Handle<StandardMaterial>>,
+    wall_mesh: Handle<Mesh>,
+    wall_material: Handle<StandardMaterial>,
 }
 
+/// Amount of materials used to approximate a gradient
+const GRADIENT_STEPS: usize = 32;
+
+#[allow(clippy::needless_arbitrary_self_type)]
 impl SnakeResourceManager {
+    pub fn theme(self: &Self) -> &Theme {
+        &self.theme
+    }
+
     pub fn apple_mesh(self: &Self) -> Handle<Mesh> {
         self.apple_mesh.clone()
     }
@@ -28,44 +44,206 @@ impl SnakeResourceManager {
         self.ball_mesh.clone()
     }
 
-    pub fn ball_material(self: &Self, material_idx: usize) -> Handle<StandardMaterial> {
-        self.ball_materials[material_idx].clone()
+    pub fn head_mesh(self: &Self) -> Handle<Mesh> {
+        self.head_mesh.clone()
+    }
+
+    /// Whether the body colors repeat along the body, instead of depending on its length
+    pub fn is_periodic(self: &Self) -> bool {
+        self.palette.periodic
+    }
+
+    /// Material of the segment at `segment_idx` (0 being the head) of a snake with `length` parts
+    pub fn segment_material(
+        self: &Self,
+        segment_idx: usize,
+        length: usize,
+    ) -> Handle<StandardMaterial> {
+        self.ball_materials[self.palette.segment_idx(segment_idx, length)].clone()
+    }
+
+    /// Segments to repaint when the body shifts by one without growing, see Palette::color_changes
+    pub fn color_changes(self: &Self, length: usize) -> Vec<usize> {
+        self.palette.color_changes(length)
+    }
+
+    pub fn wall_mesh(self: &Self) -> Handle<Mesh> {
+        self.wall_mesh.clone()
+    }
+
+    pub fn wall_material(self: &Self) -> Handle<StandardMaterial> {
+        self.wall_material.clone()
+    }
+}
+
+/// Flat colors of a theme, for whatever draws the board without materials
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub segments: Vec<Color>,
+    /// Whether the body colors repeat along the body, instead of depending on its length
+    pub periodic: bool,
+    pub apple: Color,
+    pub wall: Color,
+    pub background: Color,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Palette {
+    pub fn new(theme: &Theme) -> Self {
+        Self {
+            segments: segment_colors(&theme.segments),
+            periodic: !matches!(theme.segments, SegmentPattern::Gradient { .. }),
+            apple: theme.apple.into(),
+            wall: theme.wall.into(),
+            background: theme.background.into(),
+        }
+    }
+
+    fn segment_idx(self: &Self, segment_idx: usize, length: usize) -> usize {
+        if self.periodic {
+            segment_idx % self.segments.len()
+        } else if length > 1 {
+            segment_idx.min(length - 1) * (self.segments.len() - 1) / (length - 1)
+        } else {
+            0
+        }
+    }
+
+    /// Indices where a gradient moves on to its next color. When every segment shifts one index
+    /// down the body, these are the only ones whose color changes.
+    pub fn color_changes(self: &Self, length: usize) -> Vec<usize> {
+        if self.periodic || length < 2 {
+            return Vec::new();
+        }
+        let last_color = self.segments.len() - 1;
+        let mut changes: Vec<usize> = (1..=last_color)
+            .map(|color| (color * (length - 1)).div_ceil(last_color))
+            .collect();
+        changes.dedup();
+        changes
+    }
+
+    /// Color of the segment at `segment_idx` (0 being the head) of a snake with `length` parts
+    pub fn segment(self: &Self, segment_idx: usize, length: usize) -> Color {
+        self.segments[self.segment_idx(segment_idx, length)]
     }
+}
 
-    pub fn ball_materials_count(self: &Self) -> usize {
-        self.ball_materials.len()
+fn segment_colors(pattern: &SegmentPattern) -> Vec<Color> {
+    match pattern {
+        SegmentPattern::Rainbow { steps } => {
+            let steps = (*steps).max(1);
+            (0..steps)
+                .map(|i| Color::hsl((i as f32 / steps as f32) * 360., 1.0, 0.5))
+                .collect()
+        }
+        SegmentPattern::Gradient { head, tail } => {
+            let head = LinearRgba::from(Color::from(*head));
+            let tail = LinearRgba::from(Color::from(*tail));
+            (0..GRADIENT_STEPS)
+                .map(|i| {
+                    let t = i as f32 / (GRADIENT_STEPS - 1) as f32;
+                    Color::from(head.mix(&tail, t))
+                })
+                .collect()
+        }
+        SegmentPattern::Stripes(colors) if !colors.is_empty() => {
+            colors.iter().map(|c| Color::from(*c)).collect()
+        }
+        SegmentPattern::Stripes(_) => vec![Color::WHITE],
     }
 }
 
 impl FromWorld for SnakeResourceManager {
     fn from_world(world: &mut World) -> Self {
+        let theme = match (
+            world.get_resource::<Themes>(),
+            world.get_resource::<super::Settings>(),
+        ) {
+            (Some(themes), Some(settings)) => themes.get(&settings.theme).clone(),
+            _ => Theme::default(),
+        };
+
+        // The models are only available after the loading state, primitives are used until then
+        // or when they fail to load
+        let (apple_model, head_model) = match world.get_resource::<LoadedModels>() {
+            Some(models) => (models.apple.clone(), models.head.clone()),
+            None => (None, None),
+        };
+
         let mut mesh_resources = world.resource_mut::<Assets<Mesh>>();
-        // TODO : LOAD REAL APPLE MESH
-        let apple_mesh = mesh_resources.add(Sphere { radius: 0.5 });
         let ball_mesh = mesh_resources.add(Sphere { radius: 0.5 });
+        let apple_mesh = apple_model.unwrap_or_else(|| mesh_resources.add(Sphere { radius: 0.5 }));
+        let head_mesh = head_model.unwrap_or_else(|| ball_mesh.clone());
+        let wall_mesh = mesh_resources.add(Cuboid::from_length(1.));
 
+        let palette = Palette::new(&theme);
         let mut material_resources = world.resource_mut::<Assets<StandardMaterial>>();
-        let mut apple_materials = Vec::new();
         // TODO: LOAD DIFFERENT APPLE MATERIALS
-        apple_materials.push(material_resources.add(StandardMaterial {
-            base_color: Color::hsl(360., 1., 0.5),
+        let apple_materials = vec![material_resources.add(StandardMaterial {
+            base_color: palette.apple,
             ..Default::default()
-        }));
-
-        let mut ball_materials = Vec::new();
-        for i in 0..16 {
-            let color = Color::hsl((i as f32 / 16.) * 360., 1.0, 0.5);
-            ball_materials.push(material_resources.add(StandardMaterial {
-                base_color: color,
-                ..Default::default()
-            }))
-        }
+        })];
+
+        let ball_materials = palette
+            .segments
+            .iter()
+            .map(|color| {
+                material_resources.add(StandardMaterial {
+                    base_color: *color,
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let wall_material = material_resources.add(StandardMaterial {
+            base_color: palette.wall,
+            ..Default::default()
+        });
 
         Self {
+            theme,
+            palette,
             apple_mesh,
             apple_materials,
             ball_mesh,
+            head_mesh,
             ball_materials,
+            wall_mesh,
+            wall_material,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::theme::Rgb;
+    use super::*;
+
+    #[test]
+    fn shifted_gradient_only_changes_at_the_color_changes() {
+        let palette = Palette::new(&Theme {
+            segments: SegmentPattern::Gradient {
+                head: Rgb(1., 0., 0.),
+                tail: Rgb(0., 0., 1.),
+            },
+            ..default()
+        });
+        for length in [2, 3, 20, 32, 33, 100, 1000] {
+            let changes = palette.color_changes(length);
+            assert!(changes.len() < GRADIENT_STEPS);
+            for idx in 1..length {
+                // After a move the segment at `idx` was at `idx - 1`
+                let changed =
+                    palette.segment_idx(idx, length) != palette.segment_idx(idx - 1, length);
+                assert_eq!(changed, changes.contains(&idx), "{idx} of {length}");
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_patterns_are_never_repainted() {
+        let palette = Palette::new(&Theme::default());
+        assert!(palette.color_changes(100).is_empty());
+    }
+}
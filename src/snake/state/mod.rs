@@ -4,14 +4,24 @@ use bevy::prelude::*;
 pub enum GameState {
     #[default]
     Entrance,
+    Loading,
     Main,
     Gameplay,
     Gameover,
+    /// The snake filled every free cell of the board
+    Victory,
+    /// An online match, see the versus module
+    Versus,
+    VersusOver,
 }
 
-#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
+/// Only exists during a run, which always starts with a countdown
+#[derive(SubStates, Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[source(GameState = GameState::Gameplay)]
 pub enum GameplayState {
+    /// The board is set but the snake waits, see the pause module
     #[default]
+    Countdown,
     Running,
     Paused,
 }
@@ -0,0 +1,257 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::gameplay::{EndCause, EndRun, GameEnd};
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen};
+use super::score::RunTime;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LifetimeStats::load())
+            .init_resource::<CurrentRun>()
+            .add_menu(MenuId::Stats, stats_menu)
+            .add_systems(OnEnter(super::GameState::Gameplay), reset_run)
+            .add_systems(
+                FixedUpdate,
+                count_ticks
+                    .run_if(in_state(super::GameState::Gameplay))
+                    .run_if(in_state(super::GameplayState::Running)),
+            )
+            .add_systems(
+                Update,
+                (count_apples, record_run.after(EndRun))
+                    .chain()
+                    .run_if(in_state(super::GameState::Gameplay)),
+            )
+            .add_systems(Update, save_stats);
+    }
+}
+
+const STATS_FILE: &str = "stats.ron";
+
+/// Totals over every finished run, across sessions.
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub games_played: u32,
+    pub total_apples: u32,
+    pub longest_snake: usize,
+    /// Sum of the final length of every run, for the average
+    pub total_length: u64,
+    pub total_ticks: u64,
+    pub time_played_secs: f64,
+    pub deaths: HashMap<EndCause, u32>,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl LifetimeStats {
+    fn load() -> Self {
+        super::storage::load_ron(STATS_FILE).unwrap_or_default()
+    }
+
+    fn save(self: &Self) {
+        super::storage::save_ron(STATS_FILE, self);
+    }
+
+    pub fn average_length(self: &Self) -> f64 {
+        if self.games_played == 0 {
+            return 0.;
+        }
+        self.total_length as f64 / self.games_played as f64
+    }
+}
+
+/// Counters of the run being played, folded into the lifetime stats once it ends.
+#[derive(Resource, Default)]
+struct CurrentRun {
+    apples: u32,
+    ticks: u64,
+    recorded: bool,
+}
+
+fn reset_run(mut run: ResMut<CurrentRun>) {
+    *run = CurrentRun::default();
+}
+
+fn count_ticks(mut run: ResMut<CurrentRun>) {
+    run.ticks += 1;
+}
+
+fn count_apples(
+    mut apple_eaten_event: EventReader<super::AppleEaten>,
+    mut run: ResMut<CurrentRun>,
+) {
+    run.apples += apple_eaten_event.read().count() as u32;
+}
+
+fn record_run(
+    mut game_end_event: EventReader<GameEnd>,
+    mut run: ResMut<CurrentRun>,
+    mut stats: ResMut<LifetimeStats>,
+    parts: Query<&super::SnakePart>,
+    run_time: Res<RunTime>,
+) {
+    let Some(end) = game_end_event.read().next() else {
+        return;
+    };
+    // A run can be ended by more than one thing on the same frame, only the first counts
+    if run.recorded {
+        return;
+    }
+    run.recorded = true;
+
+    let length = parts.iter().count();
+    stats.games_played += 1;
+    stats.total_apples += run.apples;
+    stats.longest_snake = stats.longest_snake.max(length);
+    stats.total_length += length as u64;
+    stats.total_ticks += run.ticks;
+    stats.time_played_secs += run_time.0.as_secs_f64();
+    if end.cause.is_death() {
+        *stats.deaths.entry(end.cause).or_default() += 1;
+    }
+}
+
+fn save_stats(stats: Res<LifetimeStats>) {
+    if stats.is_changed() && !stats.is_added() {
+        stats.save();
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn stats_menu(world: &World) -> MenuScreen {
+    let stats = world.resource::<LifetimeStats>();
+    let localization = world.resource::<Localization>();
+    let deaths = |cause: EndCause| stats.deaths.get(&cause).copied().unwrap_or(0).to_string();
+    let lines = [
+        ("stats.games_played", stats.games_played.to_string()),
+        ("stats.total_apples", stats.total_apples.to_string()),
+        ("stats.longest_snake", stats.longest_snake.to_string()),
+        (
+            "stats.average_length",
+            format!("{:.1}", stats.average_length()),
+        ),
+        ("stats.total_ticks", stats.total_ticks.to_string()),
+        (
+            "stats.time_played",
+            format_duration(stats.time_played_secs as u64),
+        ),
+        ("stats.deaths.wall", deaths(EndCause::Wall)),
+        ("stats.deaths.self_bite", deaths(EndCause::SelfBite)),
+        ("stats.deaths.obstacle", deaths(EndCause::Obstacle)),
+        ("stats.deaths.timeout", deaths(EndCause::Timeout)),
+    ];
+    let mut items: Vec<MenuItem> = lines
+        .into_iter()
+        .map(|(key, value)| MenuItem::Label(localization.format(key, &[&value])))
+        .collect();
+    items.push(MenuItem::Button {
+        label: "common.back",
+        action: MenuAction::Back,
+    });
+    MenuScreen {
+        title: Some("stats.title"),
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SnakePart;
+    use super::*;
+    use std::time::Duration;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<GameEnd>()
+            .init_resource::<LifetimeStats>()
+            .init_resource::<CurrentRun>()
+            .init_resource::<RunTime>()
+            .add_systems(Update, record_run);
+        app
+    }
+
+    /// Plays a run of `length` segments that ends with every cause in `ends`, all on one frame
+    fn play(app: &mut App, length: usize, ticks: u64, ends: &[EndCause]) {
+        let world = app.world_mut();
+        let parts: Vec<Entity> = world
+            .query_filtered::<Entity, With<SnakePart>>()
+            .iter(world)
+            .collect();
+        for part in parts {
+            world.despawn(part);
+        }
+        for _ in 0..length {
+            world.spawn(SnakePart);
+        }
+        *world.resource_mut::<CurrentRun>() = CurrentRun {
+            apples: length as u32,
+            ticks,
+            recorded: false,
+        };
+        world.resource_mut::<RunTime>().0 = Duration::from_secs(ticks);
+        for cause in ends {
+            world.send_event(GameEnd::new(*cause));
+        }
+        app.update();
+    }
+
+    #[test]
+    fn only_the_first_end_of_a_run_counts() {
+        let mut app = app();
+        play(&mut app, 4, 10, &[EndCause::Wall, EndCause::Timeout]);
+        app.world_mut().send_event(GameEnd::new(EndCause::SelfBite));
+        app.update();
+        let stats = app.world().resource::<LifetimeStats>();
+        assert_eq!(stats.games_played, 1);
+        assert_eq!(stats.total_apples, 4);
+        assert_eq!(stats.total_ticks, 10);
+        assert_eq!(stats.time_played_secs, 10.);
+        assert_eq!(stats.deaths, HashMap::from([(EndCause::Wall, 1)]));
+    }
+
+    #[test]
+    fn only_deaths_are_counted_as_deaths() {
+        let mut app = app();
+        play(&mut app, 3, 1, &[EndCause::BoardFilled]);
+        play(&mut app, 3, 1, &[EndCause::GoalReached]);
+        play(&mut app, 3, 1, &[EndCause::Obstacle]);
+        play(&mut app, 3, 1, &[EndCause::Obstacle]);
+        let stats = app.world().resource::<LifetimeStats>();
+        assert_eq!(stats.games_played, 4);
+        assert_eq!(stats.deaths, HashMap::from([(EndCause::Obstacle, 2)]));
+    }
+
+    #[test]
+    fn longest_and_average_length() {
+        let mut app = app();
+        assert_eq!(app.world().resource::<LifetimeStats>().average_length(), 0.);
+        play(&mut app, 2, 1, &[EndCause::Wall]);
+        play(&mut app, 7, 1, &[EndCause::Wall]);
+        play(&mut app, 3, 1, &[EndCause::Wall]);
+        let stats = app.world().resource::<LifetimeStats>();
+        assert_eq!(stats.longest_snake, 7);
+        assert_eq!(stats.total_length, 12);
+        assert_eq!(stats.average_length(), 4.);
+    }
+
+    #[test]
+    fn a_frame_without_an_end_records_nothing() {
+        let mut app = app();
+        app.update();
+        assert_eq!(app.world().resource::<LifetimeStats>().games_played, 0);
+        assert!(!app.world().resource::<CurrentRun>().recorded);
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::path::PathBuf;
+
+/// Every file the game persists (settings, progress, statistics...) lives inside this directory,
+/// relative to the working directory. It can be overridden through the SNAKE_DATA_DIR variable.
+const DATA_DIR: &str = "save";
+
+pub fn data_path(file_name: &str) -> PathBuf {
+    let dir = std::env::var("SNAKE_DATA_DIR").unwrap_or_else(|_| DATA_DIR.to_owned());
+    PathBuf::from(dir).join(file_name)
+}
+
+/// Returns None when the file doesn't exist or can't be parsed, so callers can fall back to
+/// their defaults. Parsing errors are logged given that they usually mean a hand edited file.
+pub fn load_ron<T: DeserializeOwned>(file_name: &str) -> Option<T> {
+    let path = data_path(file_name);
+    let content = std::fs::read_to_string(&path).ok()?;
+    match ron::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            bevy::log::warn!("Failed to parse {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+pub fn save_ron<T: Serialize>(file_name: &str, value: &T) {
+    let path = data_path(file_name);
+    if let Some(parent) = path.parent()
+        && let Err(error) = std::fs::create_dir_all(parent)
+    {
+        bevy::log::error!("Failed to create {}: {}", parent.display(), error);
+        return;
+    }
+    let content = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(content) => content,
+        Err(error) => {
+            bevy::log::error!("Failed to serialize {}: {}", path.display(), error);
+            return;
+        }
+    };
+    if let Err(error) = std::fs::write(&path, content) {
+        bevy::log::error!("Failed to write {}: {}", path.display(), error);
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Themes::load())
+            .add_systems(Startup, apply_background)
+            .add_systems(Update, switch_theme);
+    }
+}
+
+const THEMES_DIR: &str = "assets/themes";
+
+/// sRGB color written as (r, g, b) in the theme files
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Rgb(pub f32, pub f32, pub f32);
+
+impl From<Rgb> for Color {
+    fn from(rgb: Rgb) -> Self {
+        Color::srgb(rgb.0, rgb.1, rgb.2)
+    }
+}
+
+/// How the body segments are colored, from the head (index 0) to the tail.
+#[derive(Deserialize, Debug, Clone)]
+pub enum SegmentPattern {
+    /// Walks the hue wheel in `steps` segments and starts over
+    Rainbow { steps: usize },
+    /// Blends from the head color to the tail color along the whole body
+    Gradient { head: Rgb, tail: Rgb },
+    /// Repeats the given colors
+    Stripes(Vec<Rgb>),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Lighting {
+    pub color: Rgb,
+    pub illuminance: f32,
+    pub ambient_color: Rgb,
+    pub ambient_brightness: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub segments: SegmentPattern,
+    pub apple: Rgb,
+    pub wall: Rgb,
+    pub background: Rgb,
+    pub lighting: Lighting,
+}
+
+impl Default for Theme {
+    /// The look the game had before themes existed, used when no theme file can be read.
+    fn default() -> Self {
+        Self {
+            name: "Classic".to_owned(),
+            segments: SegmentPattern::Rainbow { steps: 16 },
+            apple: Rgb(1., 0., 0.),
+            wall: Rgb(0., 0., 0.),
+            background: Rgb(0.17, 0.17, 0.17),
+            lighting: Lighting {
+                color: Rgb(1., 1., 1.),
+                illuminance: 10_000.,
+                ambient_color: Rgb(1., 1., 1.),
+                ambient_brightness: 80.,
+            },
+        }
+    }
+}
+
+/// Every theme found in assets/themes, sorted by name.
+#[derive(Resource)]
+pub struct Themes(Vec<Theme>);
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Themes {
+    pub fn load() -> Self {
+        let dir = FileAssetReader::get_base_path().join(THEMES_DIR);
+        let mut themes = Vec::new();
+        match std::fs::read_dir(&dir) {
+            Ok(entries) => {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if path.extension().is_none_or(|ext| ext != "ron") {
+                        continue;
+                    }
+                    let parsed = std::fs::read_to_string(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|content| {
+                            ron::from_str::<Theme>(&content).map_err(|e| e.to_string())
+                        });
+                    match parsed {
+                        Ok(theme) => themes.push(theme),
+                        Err(error) => warn!("Failed to load {}: {}", path.display(), error),
+                    }
+                }
+            }
+            Err(error) => warn!("Failed to read {}: {}", dir.display(), error),
+        }
+        if themes.is_empty() {
+            themes.push(Theme::default());
+        }
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        Self(themes)
+    }
+
+    /// Falls back to the first theme when the name is unknown
+    pub fn get(self: &Self, name: &str) -> &Theme {
+        self.0.iter().find(|t| t.name == name).unwrap_or(&self.0[0])
+    }
+
+    pub fn names(self: &Self) -> Vec<String> {
+        self.0.iter().map(|t| t.name.clone()).collect()
+    }
+}
+
+fn apply_background(mut commands: Commands, themes: Res<Themes>, settings: Res<super::Settings>) {
+    let theme = themes.get(&settings.theme);
+    commands.insert_resource(ClearColor(theme.background.into()));
+    commands.insert_resource(AmbientLight {
+        color: theme.lighting.ambient_color.into(),
+        brightness: theme.lighting.ambient_brightness,
+        ..default()
+    });
+}
+
+/// Rebuilds the materials when another theme is picked. Themes are only picked from the menus,
+/// so there are no snake entities around holding the old handles.
+fn switch_theme(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    settings: Res<super::Settings>,
+    snake_resources: Option<Res<super::SnakeResourceManager>>,
+) {
+    let Some(snake_resources) = snake_resources else {
+        return;
+    };
+    if !settings.is_changed() || snake_resources.theme().name == themes.get(&settings.theme).name {
+        return;
+    }
+    commands.queue(|world: &mut World| {
+        let snake_resources = super::SnakeResourceManager::from_world(world);
+        world.insert_resource(snake_resources);
+    });
+    apply_background(commands, themes, settings);
+}
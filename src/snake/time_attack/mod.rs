@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+use super::GameMode;
+use super::gameplay::{EndCause, EndRun, GameEnd, WallHit};
+
+pub struct TimeAttackPlugin;
+
+impl Plugin for TimeAttackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeLeft>()
+            .add_systems(OnEnter(super::GameState::Gameplay), reset_clock)
+            .add_systems(
+                Update,
+                (tick_clock.in_set(EndRun), apply_wall_penalty)
+                    .run_if(in_state(super::GameState::Gameplay))
+                    .run_if(in_state(super::GameplayState::Running))
+                    .run_if(resource_equals(GameMode::TimeAttack)),
+            );
+    }
+}
+
+const TIME_LIMIT: Duration = Duration::from_secs(120);
+const WALL_PENALTY: Duration = Duration::from_secs(10);
+
+/// Remaining time of a Time Attack run.
+#[derive(Resource, Default)]
+pub struct TimeLeft(pub Duration);
+
+fn reset_clock(mut time_left: ResMut<TimeLeft>) {
+    time_left.0 = TIME_LIMIT;
+}
+
+fn tick_clock(
+    time: Res<Time>,
+    mut time_left: ResMut<TimeLeft>,
+    mut game_end_event: EventWriter<GameEnd>,
+) {
+    time_left.0 = time_left.0.saturating_sub(time.delta());
+    if time_left.0.is_zero() {
+        game_end_event.write(GameEnd::new(EndCause::Timeout));
+    }
+}
+
+fn apply_wall_penalty(mut wall_hit_event: EventReader<WallHit>, mut time_left: ResMut<TimeLeft>) {
+    for _ in wall_hit_event.read() {
+        time_left.0 = time_left.0.saturating_sub(WALL_PENALTY);
+    }
+}
@@ -0,0 +1,1214 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+use super::body::SnakeBody;
+use super::gameplay::{SnakeHead, find_collision};
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen, MenuStack};
+use super::netplay::{
+    Clock, INPUT_DELAY, LinkConditions, Lockstep, Role, SimulatedTransport, UdpTransport,
+};
+use super::occupancy::{Occupancy, Occupant};
+use super::rules::GameRules;
+
+/// Online versus: two snakes on the same board, the last one standing wins. Matches run on
+/// their own deterministic simulation, kept in sync by the netplay lockstep, instead of the
+/// gameplay systems.
+pub struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LinkError>()
+            .add_menu(MenuId::Versus, versus_menu)
+            .add_menu(MenuId::VersusOver, result_menu)
+            .add_systems(OnEnter(super::GameState::Versus), (spawn_scene, set_pace))
+            .add_systems(
+                FixedUpdate,
+                run_lockstep.run_if(in_state(super::GameState::Versus)),
+            )
+            .add_systems(
+                Update,
+                (
+                    read_input,
+                    spawn_walls,
+                    sync_pieces,
+                    update_status,
+                    finish_match,
+                )
+                    .run_if(in_state(super::GameState::Versus)),
+            )
+            .add_systems(OnExit(super::GameState::Versus), leave_match)
+            .add_systems(
+                OnEnter(super::GameState::VersusOver),
+                (open_result, spawn_camera),
+            )
+            .add_systems(
+                OnExit(super::GameState::VersusOver),
+                (despawn_camera, close_menu),
+            );
+    }
+}
+
+/// Overridden through SNAKE_VERSUS_PORT when hosting, joining uses SNAKE_VERSUS_PEER
+const DEFAULT_PORT: u16 = 7777;
+const TICK_HZ: f64 = 10.;
+/// Ticks between state hash comparisons
+const CHECKPOINT_INTERVAL: u32 = 30;
+/// Lets the crash be seen, and the last inputs reach the other peer, before leaving the board
+const FINISH_DELAY: Duration = Duration::from_millis(1500);
+/// Ticks without progress before telling the player the other peer is late
+const STALL_NOTICE: u32 = 5;
+const PRACTICE_LINK: LinkConditions = LinkConditions {
+    loss: 0.2,
+    latency: Duration::from_millis(80),
+    jitter: Duration::from_millis(40),
+};
+const RIVAL_COLOR: Color = Color::srgb(0.85, 0.3, 0.3);
+
+/// Inputs as they travel: 0 keeps going, the rest turn
+const KEEP: u8 = 0;
+const DIRECTIONS: [IVec2; 4] = [IVec2::Y, IVec2::NEG_Y, IVec2::NEG_X, IVec2::X];
+
+fn direction(input: u8) -> Option<IVec2> {
+    DIRECTIONS.get((input as usize).checked_sub(1)?).copied()
+}
+
+fn input(direction: IVec2) -> u8 {
+    DIRECTIONS
+        .iter()
+        .position(|candidate| *candidate == direction)
+        .map_or(KEEP, |idx| idx as u8 + 1)
+}
+
+#[derive(Clone)]
+struct Rival {
+    head: SnakeHead,
+    body: SnakeBody,
+    apples: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum Outcome {
+    Winner(usize),
+    Draw,
+}
+
+/// The whole match state. Only depends on the seed, the rules and the inputs, so every peer
+/// computes the same one. It travels as a SimState, the board is rebuilt from the snakes.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(into = "SimState", try_from = "SimState")]
+struct VersusSim {
+    tick: u32,
+    seed: u64,
+    /// Random draws made so far, each one is seeded from it
+    draws: u64,
+    rules: GameRules,
+    rivals: [Rival; 2],
+    apples: Vec<IVec2>,
+    occupancy: Occupancy,
+    outcome: Option<Outcome>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RivalState {
+    /// From the head to the tail
+    cells: Vec<IVec2>,
+    direction: IVec2,
+    apples: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SimState {
+    tick: u32,
+    seed: u64,
+    draws: u64,
+    rules: GameRules,
+    rivals: [RivalState; 2],
+    apples: Vec<IVec2>,
+    outcome: Option<Outcome>,
+}
+
+impl From<VersusSim> for SimState {
+    fn from(sim: VersusSim) -> Self {
+        Self {
+            tick: sim.tick,
+            seed: sim.seed,
+            draws: sim.draws,
+            rules: sim.rules,
+            rivals: sim.rivals.map(|rival| RivalState {
+                cells: rival.body.iter().map(|segment| segment.cell).collect(),
+                direction: rival.head.direction().as_ivec2(),
+                apples: rival.apples,
+            }),
+            apples: sim.apples,
+            outcome: sim.outcome,
+        }
+    }
+}
+
+impl TryFrom<SimState> for VersusSim {
+    type Error = String;
+
+    fn try_from(state: SimState) -> Result<Self, String> {
+        let rules = state.rules.validated("versus");
+        let mut occupancy = Occupancy::new(rules.side);
+        for y in -1..=rules.side + 1 {
+            for x in -1..=rules.side + 1 {
+                let cell = IVec2::new(x, y);
+                if !rules.is_inside(cell) {
+                    occupancy.set(cell, Some(Occupant::Wall(Entity::PLACEHOLDER)));
+                }
+            }
+        }
+        let mut rivals = Vec::with_capacity(state.rivals.len());
+        for rival in state.rivals {
+            let Some((head, behind)) = rival.cells.split_first() else {
+                return Err("a snake has no cells".to_owned());
+            };
+            if let Some(cell) = rival.cells.iter().find(|cell| !rules.is_inside(**cell)) {
+                return Err(format!("a snake is off the board, on {}", cell));
+            }
+            if !DIRECTIONS.contains(&rival.direction) {
+                return Err(format!("a snake heads {}", rival.direction));
+            }
+            let mut body = SnakeBody::new(Entity::PLACEHOLDER, *head);
+            occupancy.add_segment(*head);
+            for cell in behind {
+                body.push(Entity::PLACEHOLDER, *cell);
+                occupancy.add_segment(*cell);
+            }
+            rivals.push(Rival {
+                head: SnakeHead::new(rival.direction.as_vec2()),
+                body,
+                apples: rival.apples,
+            });
+        }
+        for apple in &state.apples {
+            if !rules.is_inside(*apple) || occupancy.get(*apple).is_some() {
+                return Err(format!("an apple is on a taken cell, {}", apple));
+            }
+            occupancy.set(*apple, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+        }
+        Ok(Self {
+            tick: state.tick,
+            seed: state.seed,
+            draws: state.draws,
+            rules,
+            rivals: rivals
+                .try_into()
+                .map_err(|_| "a match has two snakes".to_owned())?,
+            apples: state.apples,
+            occupancy,
+            outcome: state.outcome,
+        })
+    }
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl VersusSim {
+    fn new(seed: u64, rules: GameRules) -> Self {
+        let side = rules.side;
+        let starts = [
+            (IVec2::new(side / 4, side / 2), IVec2::Y),
+            (IVec2::new(side - side / 4, side / 2), IVec2::NEG_Y),
+        ];
+        let state = SimState {
+            tick: 0,
+            seed,
+            draws: 0,
+            rules,
+            // Laid out behind the head, what doesn't fit is stacked against the wall and unfolds
+            // as the snake moves
+            rivals: starts.map(|(head, direction)| RivalState {
+                cells: (0..rules.start_length as i32)
+                    .map(|idx| head - direction * idx.min(side / 2))
+                    .collect(),
+                direction,
+                apples: 0,
+            }),
+            apples: Vec::new(),
+            outcome: None,
+        };
+        let mut sim = Self::try_from(state).expect("The starting snakes are on the board");
+        sim.spawn_apples();
+        sim
+    }
+
+    /// The same state with the board rebuilt from scratch, as a peer loading it gets it. Free
+    /// cells are drawn from in the order the board keeps them, which only a rebuild resets.
+    fn rebuilt(self: &Self) -> Self {
+        Self::try_from(SimState::from(self.clone())).expect("A running match always loads back")
+    }
+
+    fn spawn_apples(self: &mut Self) {
+        while self.apples.len() < self.rules.apples {
+            let rng = super::RngResource::seeded(
+                self.seed ^ self.draws.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            );
+            let Some(cell) = self.occupancy.random_free_cell(&rng) else {
+                return;
+            };
+            self.draws += 1;
+            self.occupancy
+                .set(cell, Some(Occupant::Apple(Entity::PLACEHOLDER)));
+            self.apples.push(cell);
+        }
+    }
+
+    /// Both snakes move at once, by the same rules as a single player run. Running into a wall
+    /// or a body kills, and so does a head on collision, for both snakes. The match ends before
+    /// anyone moves.
+    fn step(self: &mut Self, inputs: [u8; 2]) {
+        if self.outcome.is_some() {
+            return;
+        }
+        for (rival, input) in self.rivals.iter_mut().zip(inputs) {
+            if let Some(direction) = direction(input) {
+                rival.head.turn(direction.as_vec2());
+            }
+        }
+        self.tick += 1;
+        let nexts = self.rivals.each_ref().map(|rival| {
+            self.rules
+                .next_cell(rival.body.head().cell, rival.head.direction().as_ivec2())
+        });
+        let mut dead = self.rivals.each_ref().map(|rival| {
+            find_collision(&rival.head, &rival.body, &self.occupancy, &self.rules).is_some()
+        });
+        if nexts[0] == nexts[1] {
+            dead = [true, true];
+        }
+        self.outcome = match dead {
+            [true, true] => Some(Outcome::Draw),
+            [true, false] => Some(Outcome::Winner(1)),
+            [false, true] => Some(Outcome::Winner(0)),
+            [false, false] => None,
+        };
+        if self.outcome.is_some() {
+            return;
+        }
+        let eats = nexts.map(|next| matches!(self.occupancy.get(next), Some(Occupant::Apple(_))));
+        for (rival, next) in self.rivals.iter_mut().zip(nexts) {
+            self.occupancy.add_segment(next);
+            rival.body.advance(next);
+            self.occupancy.remove_segment(rival.body.vacated());
+        }
+        for (rival, eats) in self.rivals.iter_mut().zip(eats) {
+            if !eats {
+                continue;
+            }
+            rival.apples += 1;
+            for _ in 0..self.rules.growth {
+                let segment = rival.body.grow(Entity::PLACEHOLDER);
+                self.occupancy.add_segment(segment.cell);
+            }
+        }
+        self.apples.retain(|apple| !nexts.contains(apple));
+        self.spawn_apples();
+    }
+
+    /// FNV-1a over the state, written by hand so it is the same on every platform
+    fn state_hash(self: &Self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |value: i64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        feed(self.tick as i64);
+        feed(self.draws as i64);
+        feed(self.apples.len() as i64);
+        for apple in &self.apples {
+            feed(apple.x as i64);
+            feed(apple.y as i64);
+        }
+        for rival in &self.rivals {
+            feed(rival.body.len() as i64);
+            for segment in rival.body.iter() {
+                feed(segment.cell.x as i64);
+                feed(segment.cell.y as i64);
+            }
+            let direction = rival.head.direction().as_ivec2();
+            feed(direction.x as i64);
+            feed(direction.y as i64);
+            feed(rival.apples as i64);
+        }
+        hash
+    }
+}
+
+/// Input of the practice opponent. It only decides once its previous input was applied, looking
+/// at the board as it will be by the time the new one is.
+fn bot_input(sim: &VersusSim, idx: usize) -> u8 {
+    if !sim.tick.is_multiple_of(INPUT_DELAY + 1) {
+        return KEEP;
+    }
+    let mut ahead = sim.clone();
+    for _ in 0..INPUT_DELAY {
+        ahead.step([KEEP, KEEP]);
+    }
+    steer(&ahead, idx)
+}
+
+/// Turns towards the nearest apple, avoiding whatever is right ahead
+fn steer(sim: &VersusSim, idx: usize) -> u8 {
+    let rival = &sim.rivals[idx];
+    let head = rival.body.head().cell;
+    let distance = |from: IVec2, to: IVec2| (to - from).abs().element_sum();
+    let apple = sim
+        .apples
+        .iter()
+        .copied()
+        .min_by_key(|apple| distance(head, *apple))
+        .unwrap_or(head);
+    let ahead = rival.head.direction().as_ivec2();
+    [ahead, ahead.perp(), -ahead.perp()]
+        .into_iter()
+        .map(|direction| (direction, sim.rules.next_cell(head, direction)))
+        .filter(|(_, next)| matches!(sim.occupancy.get(*next), None | Some(Occupant::Apple(_))))
+        .min_by_key(|(_, next)| distance(*next, apple))
+        .map_or(KEEP, |(direction, _)| input(direction))
+}
+
+/// One side of the match, with its own copy of the simulation
+struct Peer {
+    session: Lockstep,
+    sim: Option<VersusSim>,
+    /// Latest input of the player, until the lockstep takes it
+    pending: u8,
+    stalled: u32,
+}
+
+#[allow(clippy::needless_arbitrary_self_type)]
+impl Peer {
+    fn new(session: Lockstep) -> Self {
+        Self {
+            session,
+            sim: None,
+            pending: KEEP,
+            stalled: 0,
+        }
+    }
+
+    fn snake_idx(self: &Self) -> usize {
+        match self.session.role() {
+            Role::Host => 0,
+            Role::Guest => 1,
+        }
+    }
+
+    fn tick(self: &mut Self) {
+        self.session.poll();
+        self.load_snapshot();
+        if self.session.take_resync_request()
+            && let Some(sim) = &mut self.sim
+        {
+            // Rebuilt on this side too, so both boards draw apples from the same free cells
+            *sim = sim.rebuilt();
+            match ron::to_string(sim) {
+                Ok(state) => self.session.send_snapshot(sim.tick, state),
+                Err(error) => error!("Failed to serialize the match: {}", error),
+            }
+        }
+        if self.sim.is_none()
+            && let Some(seed) = self.session.seed()
+            && let Some(setup) = self.session.setup()
+        {
+            let rules = ron::from_str::<GameRules>(setup).unwrap_or_else(|error| {
+                warn!(
+                    "Failed to read the rules of the host, using the default ones: {}",
+                    error
+                );
+                GameRules::DEFAULT
+            });
+            self.sim = Some(VersusSim::new(seed, rules));
+        }
+        let Some(sim) = &mut self.sim else {
+            return;
+        };
+        if sim.outcome.is_none() && self.session.schedule(sim.tick, self.pending) {
+            self.pending = KEEP;
+        }
+        self.session.send();
+        if sim.outcome.is_some() {
+            return;
+        }
+        match self.session.inputs(sim.tick) {
+            Some(inputs) => {
+                advance(sim, &mut self.session, inputs);
+                self.stalled = 0;
+            }
+            None => self.stalled += 1,
+        }
+    }
+
+    /// Replaces the simulation by the host one, then replays the ticks it was already past
+    fn load_snapshot(self: &mut Self) {
+        let Some((tick, state)) = self.session.take_snapshot() else {
+            return;
+        };
+        let sim = match ron::from_str::<VersusSim>(&state) {
+            Ok(sim) => sim,
+            Err(error) => {
+                error!("Failed to load the match state: {}", error);
+                return;
+            }
+        };
+        info!("Resynced to the host state at tick {}", tick);
+        let reached = self.sim.as_ref().map_or(0, |current| current.tick);
+        let sim = self.sim.insert(sim);
+        while sim.tick < reached
+            && let Some(inputs) = self.session.inputs(sim.tick)
+        {
+            advance(sim, &mut self.session, inputs);
+        }
+    }
+}
+
+fn advance(sim: &mut VersusSim, session: &mut Lockstep, inputs: [u8; 2]) {
+    sim.step(inputs);
+    if sim.tick.is_multiple_of(CHECKPOINT_INTERVAL) {
+        session.checkpoint(sim.tick, sim.state_hash());
+    }
+}
+
+#[derive(Resource)]
+struct Match {
+    me: Peer,
+    /// The other side of a practice match, running in-process
+    bot: Option<Peer>,
+    finish: Option<Timer>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MatchResult {
+    Won,
+    Lost,
+    Draw,
+    Disconnected,
+}
+
+#[derive(Resource)]
+struct VersusResult {
+    result: MatchResult,
+    /// Apples of the player, then of the opponent
+    apples: [u32; 2],
+}
+
+/// Why the last attempt to host or join failed, shown on the versus screen
+#[derive(Resource, Default)]
+struct LinkError(Option<String>);
+
+fn start(world: &mut World, session: Lockstep, bot: Option<Lockstep>) {
+    world.resource_mut::<LinkError>().0 = None;
+    world.insert_resource(Match {
+        me: Peer::new(session),
+        bot: bot.map(Peer::new),
+        finish: None,
+    });
+    world
+        .resource_mut::<NextState<super::GameState>>()
+        .set(super::GameState::Versus);
+}
+
+fn port() -> u16 {
+    std::env::var("SNAKE_VERSUS_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT)
+}
+
+fn peer_address() -> String {
+    std::env::var("SNAKE_VERSUS_PEER").unwrap_or_else(|_| format!("127.0.0.1:{}", DEFAULT_PORT))
+}
+
+/// Matches play on the rules of the host, sent to the guest along with the seed
+fn setup(world: &World) -> String {
+    ron::to_string(world.resource::<GameRules>()).expect("Rules always serialize")
+}
+
+fn host(world: &mut World) {
+    let port = port();
+    match UdpTransport::host(port) {
+        Ok(transport) => {
+            let seed = world.resource::<super::RngResource>().random_seed();
+            let setup = setup(world);
+            start(
+                world,
+                Lockstep::host(Box::new(transport), Clock::real(), seed, setup, INPUT_DELAY),
+                None,
+            );
+        }
+        Err(error) => {
+            error!("Failed to listen on port {}: {}", port, error);
+            world.resource_mut::<LinkError>().0 = Some(error.to_string());
+        }
+    }
+}
+
+fn join(world: &mut World) {
+    let address = peer_address();
+    let transport = address
+        .to_socket_addrs()
+        .and_then(|mut addresses| {
+            addresses
+                .next()
+                .ok_or_else(|| std::io::Error::other("no address found"))
+        })
+        .and_then(UdpTransport::join);
+    match transport {
+        Ok(transport) => start(
+            world,
+            Lockstep::guest(Box::new(transport), Clock::real()),
+            None,
+        ),
+        Err(error) => {
+            error!("Failed to join {}: {}", address, error);
+            world.resource_mut::<LinkError>().0 = Some(error.to_string());
+        }
+    }
+}
+
+/// Plays against a bot over a simulated link that loses and delays packets
+fn practice(world: &mut World) {
+    let rng = world.resource::<super::RngResource>();
+    let (seed, link_seed) = (rng.random_seed(), rng.random_seed());
+    let setup = setup(world);
+    let clock = Clock::real();
+    let (host_link, guest_link) = SimulatedTransport::pair(PRACTICE_LINK, link_seed, clock.clone());
+    start(
+        world,
+        Lockstep::host(Box::new(host_link), clock.clone(), seed, setup, INPUT_DELAY),
+        Some(Lockstep::guest(Box::new(guest_link), clock)),
+    );
+}
+
+fn versus_menu(world: &World) -> MenuScreen {
+    let localization = world.resource::<Localization>();
+    let mut items = vec![
+        MenuItem::Button {
+            label: "versus.host",
+            action: MenuAction::Run(host),
+        },
+        MenuItem::Button {
+            label: "versus.join",
+            action: MenuAction::Run(join),
+        },
+        MenuItem::Button {
+            label: "versus.practice",
+            action: MenuAction::Run(practice),
+        },
+        MenuItem::Label(
+            localization.format("versus.address", &[&port().to_string(), &peer_address()]),
+        ),
+    ];
+    if let Some(error) = &world.resource::<LinkError>().0 {
+        items.push(MenuItem::Label(
+            localization.format("versus.link_error", &[error]),
+        ));
+    }
+    items.push(MenuItem::Button {
+        label: "common.back",
+        action: MenuAction::Back,
+    });
+    MenuScreen {
+        title: Some("versus.title"),
+        items,
+    }
+}
+
+/// Everything on the board while a match is on
+#[derive(Component)]
+struct VersusScene;
+
+#[derive(Component)]
+struct StatusText;
+
+#[derive(Resource)]
+struct Pieces {
+    snakes: [Vec<Entity>; 2],
+    apples: Vec<Entity>,
+    /// The walls go up once the rules of the match are known
+    walls: bool,
+    rival_material: Handle<StandardMaterial>,
+}
+
+fn set_pace(mut fixed_time: ResMut<Time<Fixed>>) {
+    fixed_time.set_timestep_hz(TICK_HZ);
+}
+
+fn spawn_scene(
+    mut commands: Commands,
+    snake_resources: Res<super::SnakeResourceManager>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let lighting = &snake_resources.theme().lighting;
+    commands.spawn((
+        VersusScene,
+        DirectionalLight {
+            color: lighting.color.into(),
+            illuminance: lighting.illuminance,
+            ..default()
+        },
+    ));
+    commands.spawn((VersusScene, Camera3d::default()));
+    commands.insert_resource(Pieces {
+        snakes: [Vec::new(), Vec::new()],
+        apples: Vec::new(),
+        walls: false,
+        rival_material: materials.add(StandardMaterial::from(RIVAL_COLOR)),
+    });
+    commands.spawn((
+        VersusScene,
+        StatusText,
+        Text::default(),
+        TextColor(Color::srgb(1., 1., 1.)),
+        TextShadow::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+    ));
+}
+
+fn read_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut versus: ResMut<Match>,
+    mut next_state: ResMut<NextState<super::GameState>>,
+) {
+    if input.just_pressed(KeyCode::Escape) {
+        next_state.set(super::GameState::Main);
+        return;
+    }
+    let keys = [
+        (KeyCode::KeyW, IVec2::Y),
+        (KeyCode::KeyS, IVec2::NEG_Y),
+        (KeyCode::KeyA, IVec2::NEG_X),
+        (KeyCode::KeyD, IVec2::X),
+    ];
+    for (key, direction) in keys {
+        if input.just_pressed(key) {
+            versus.me.pending = self::input(direction);
+        }
+    }
+}
+
+fn run_lockstep(mut versus: ResMut<Match>) {
+    if let Some(bot) = &mut versus.bot {
+        if let Some(sim) = &bot.sim {
+            bot.pending = bot_input(sim, bot.snake_idx());
+        }
+        bot.tick();
+    }
+    versus.me.tick();
+}
+
+fn spawn_walls(
+    mut commands: Commands,
+    versus: Res<Match>,
+    mut pieces: ResMut<Pieces>,
+    snake_resources: Res<super::SnakeResourceManager>,
+) {
+    let Some(sim) = &versus.me.sim else {
+        return;
+    };
+    if pieces.walls {
+        return;
+    }
+    pieces.walls = true;
+    let rules = sim.rules;
+    for y in -1..=rules.side + 1 {
+        for x in -1..=rules.side + 1 {
+            let cell = IVec2::new(x, y);
+            if !rules.is_inside(cell) {
+                commands.spawn((
+                    VersusScene,
+                    Transform::from_translation(rules.cell_position(cell)),
+                    Mesh3d(snake_resources.wall_mesh()),
+                    MeshMaterial3d(snake_resources.wall_material()),
+                ));
+            }
+        }
+    }
+}
+
+/// Moves the pieces to where the simulation has them, spawning and despawning segments and
+/// apples as their count changes.
+fn sync_pieces(
+    mut commands: Commands,
+    versus: Res<Match>,
+    mut pieces: ResMut<Pieces>,
+    mut transforms: Query<&mut Transform, With<VersusScene>>,
+    snake_resources: Res<super::SnakeResourceManager>,
+) {
+    let Some(sim) = &versus.me.sim else {
+        return;
+    };
+    let mine = versus.me.snake_idx();
+    let rival_material = pieces.rival_material.clone();
+    for (idx, rival) in sim.rivals.iter().enumerate() {
+        let entities = &mut pieces.snakes[idx];
+        let length = rival.body.len();
+        let resized = entities.len() != length;
+        while entities.len() > length {
+            if let Some(entity) = entities.pop() {
+                commands.entity(entity).despawn();
+            }
+        }
+        for (segment, part) in rival.body.iter().enumerate() {
+            let translation = sim.rules.cell_position(part.cell);
+            let material = if idx == mine {
+                snake_resources.segment_material(segment, length)
+            } else {
+                rival_material.clone()
+            };
+            match entities.get(segment) {
+                Some(entity) => {
+                    if let Ok(mut transform) = transforms.get_mut(*entity) {
+                        transform.translation = translation;
+                    }
+                    // Gradients depend on the length
+                    if resized && idx == mine {
+                        commands.entity(*entity).insert(MeshMaterial3d(material));
+                    }
+                }
+                None => {
+                    let mesh = if segment == 0 {
+                        snake_resources.head_mesh()
+                    } else {
+                        snake_resources.ball_mesh()
+                    };
+                    let entity = commands
+                        .spawn((
+                            VersusScene,
+                            Transform::from_translation(translation),
+                            Mesh3d(mesh),
+                            MeshMaterial3d(material),
+                        ))
+                        .id();
+                    entities.push(entity);
+                }
+            }
+        }
+    }
+    while pieces.apples.len() > sim.apples.len() {
+        if let Some(entity) = pieces.apples.pop() {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (idx, apple) in sim.apples.iter().enumerate() {
+        let translation = sim.rules.cell_position(*apple);
+        match pieces.apples.get(idx) {
+            Some(entity) => {
+                if let Ok(mut transform) = transforms.get_mut(*entity) {
+                    transform.translation = translation;
+                }
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        VersusScene,
+                        Transform::from_translation(translation),
+                        Mesh3d(snake_resources.apple_mesh()),
+                        MeshMaterial3d(snake_resources.apple_materials(0)),
+                    ))
+                    .id();
+                pieces.apples.push(entity);
+            }
+        }
+    }
+}
+
+fn update_status(
+    versus: Res<Match>,
+    mut text: Single<&mut Text, With<StatusText>>,
+    localization: Res<Localization>,
+) {
+    let me = &versus.me;
+    let status = match &me.sim {
+        None if me.session.role() == Role::Host => localization.get("versus.waiting_guest"),
+        None => localization.get("versus.connecting"),
+        Some(_) if me.stalled >= STALL_NOTICE => localization.get("versus.waiting"),
+        Some(sim) => {
+            let mine = me.snake_idx();
+            localization.format(
+                "versus.score",
+                &[
+                    &sim.rivals[mine].apples.to_string(),
+                    &sim.rivals[1 - mine].apples.to_string(),
+                ],
+            )
+        }
+    };
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+fn finish_match(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut versus: ResMut<Match>,
+    mut next_state: ResMut<NextState<super::GameState>>,
+) {
+    let mine = versus.me.snake_idx();
+    let sim = versus.me.sim.as_ref();
+    let apples = sim.map_or([0, 0], |sim| {
+        [sim.rivals[mine].apples, sim.rivals[1 - mine].apples]
+    });
+    let result = match sim.and_then(|sim| sim.outcome) {
+        Some(Outcome::Draw) => MatchResult::Draw,
+        Some(Outcome::Winner(winner)) if winner == mine => MatchResult::Won,
+        Some(Outcome::Winner(_)) => MatchResult::Lost,
+        None if versus.me.session.is_disconnected() => {
+            commands.insert_resource(VersusResult {
+                result: MatchResult::Disconnected,
+                apples,
+            });
+            next_state.set(super::GameState::VersusOver);
+            return;
+        }
+        None => return,
+    };
+    let finish = versus
+        .finish
+        .get_or_insert_with(|| Timer::new(FINISH_DELAY, TimerMode::Once));
+    if finish.tick(time.delta()).just_finished() {
+        commands.insert_resource(VersusResult { result, apples });
+        next_state.set(super::GameState::VersusOver);
+    }
+}
+
+fn leave_match(
+    mut commands: Commands,
+    mut versus: ResMut<Match>,
+    scene: Query<Entity, With<VersusScene>>,
+) {
+    versus.me.session.close();
+    commands.remove_resource::<Match>();
+    commands.remove_resource::<Pieces>();
+    for entity in scene {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn result_menu(world: &World) -> MenuScreen {
+    let Some(result) = world.get_resource::<VersusResult>() else {
+        return MenuScreen {
+            title: None,
+            items: Vec::new(),
+        };
+    };
+    let title = match result.result {
+        MatchResult::Won => "versus.won",
+        MatchResult::Lost => "versus.lost",
+        MatchResult::Draw => "versus.draw",
+        MatchResult::Disconnected => "versus.disconnected",
+    };
+    let apples = result.apples.map(|apples| apples.to_string());
+    MenuScreen {
+        title: Some(title),
+        items: vec![
+            MenuItem::Label(
+                world
+                    .resource::<Localization>()
+                    .format("versus.final_score", &[&apples[0], &apples[1]]),
+            ),
+            MenuItem::Button {
+                label: "gameover.main_menu",
+                action: MenuAction::Run(|world| {
+                    world
+                        .resource_mut::<NextState<super::GameState>>()
+                        .set(super::GameState::Main)
+                }),
+            },
+        ],
+    }
+}
+
+fn open_result(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.open(MenuId::VersusOver);
+}
+
+fn close_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.clear();
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+fn despawn_camera(mut commands: Commands, camera: Single<Entity, With<Camera2d>>) {
+    commands.entity(*camera).despawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rules::WallBehavior;
+    use super::*;
+    use bevy::platform::collections::HashMap;
+
+    const TICK: Duration = Duration::from_millis(100);
+    const LOSSY_LINK: LinkConditions = LinkConditions {
+        loss: 0.3,
+        latency: Duration::from_millis(150),
+        jitter: Duration::from_millis(200),
+    };
+
+    struct Link {
+        host: Peer,
+        guest: Peer,
+        clock: Clock,
+        /// State hash of each side after every tick it went through
+        hashes: [HashMap<u32, u64>; 2],
+    }
+
+    #[allow(clippy::needless_arbitrary_self_type)]
+    impl Link {
+        fn new(conditions: LinkConditions, seed: u64) -> Self {
+            Self::with_rules(conditions, seed, GameRules::DEFAULT)
+        }
+
+        fn with_rules(conditions: LinkConditions, seed: u64, rules: GameRules) -> Self {
+            let clock = Clock::manual();
+            let (host_link, guest_link) = SimulatedTransport::pair(conditions, seed, clock.clone());
+            Self {
+                host: Peer::new(Lockstep::host(
+                    Box::new(host_link),
+                    clock.clone(),
+                    seed,
+                    ron::to_string(&rules).unwrap(),
+                    INPUT_DELAY,
+                )),
+                guest: Peer::new(Lockstep::guest(Box::new(guest_link), clock.clone())),
+                clock,
+                hashes: Default::default(),
+            }
+        }
+
+        /// Both peers play like the practice bot
+        fn run(self: &mut Self, ticks: u32) {
+            for _ in 0..ticks {
+                self.clock.advance(TICK);
+                for (side, peer) in [&mut self.host, &mut self.guest].into_iter().enumerate() {
+                    if let Some(sim) = &peer.sim {
+                        peer.pending = bot_input(sim, peer.snake_idx());
+                    }
+                    peer.tick();
+                    if let Some(sim) = &peer.sim {
+                        self.hashes[side].insert(sim.tick, sim.state_hash());
+                    }
+                }
+            }
+        }
+
+        /// Ticks both sides went through, with whether their states agreed on each
+        fn agreement(self: &Self) -> Vec<(u32, bool)> {
+            let mut ticks: Vec<_> = self.hashes[0]
+                .iter()
+                .filter_map(|(tick, hash)| Some((*tick, self.hashes[1].get(tick)? == hash)))
+                .collect();
+            ticks.sort();
+            ticks
+        }
+    }
+
+    #[test]
+    fn peers_agree_over_a_lossy_link() {
+        let mut common = 0;
+        for seed in 0..8 {
+            let mut link = Link::new(LOSSY_LINK, seed);
+            link.run(400);
+            let agreement = link.agreement();
+            assert!(agreement.iter().all(|(_, agree)| *agree), "seed {seed}");
+            common += agreement.len();
+            // Whoever lost, both sides saw the same end
+            let [host, guest] = [&link.host, &link.guest].map(|peer| peer.sim.as_ref().unwrap());
+            assert_eq!(host.outcome, guest.outcome, "seed {seed}");
+            if host.outcome.is_some() {
+                assert_eq!(host.state_hash(), guest.state_hash(), "seed {seed}");
+            }
+            assert!(!link.host.session.is_disconnected());
+            assert!(!link.guest.session.is_disconnected());
+        }
+        assert!(common > 200, "only {common} common ticks");
+    }
+
+    #[test]
+    fn the_guest_plays_on_the_rules_of_the_host() {
+        let rules = GameRules {
+            walls: WallBehavior::Wrap,
+            apples: 3,
+            growth: 2,
+            start_length: 4,
+            ..GameRules::DEFAULT
+        };
+        for seed in 0..4 {
+            let mut link = Link::with_rules(LOSSY_LINK, seed, rules);
+            link.run(200);
+            assert!(
+                link.agreement().iter().all(|(_, agree)| *agree),
+                "seed {seed}"
+            );
+            let [host, guest] = [&link.host, &link.guest].map(|peer| peer.sim.as_ref().unwrap());
+            assert_eq!(host.rules, rules);
+            assert_eq!(guest.rules, rules);
+            assert_eq!(host.apples.len(), 3);
+            host.occupancy.assert_consistent();
+        }
+    }
+
+    fn played(rules: GameRules, ticks: u32) -> VersusSim {
+        let mut sim = VersusSim::new(9, rules);
+        for _ in 0..ticks {
+            let inputs = [bot_input(&sim, 0), bot_input(&sim, 1)];
+            sim.step(inputs);
+        }
+        sim
+    }
+
+    #[test]
+    fn snapshots_load_back_the_same_match() {
+        let sim = played(GameRules::DEFAULT, 60);
+        let mut loaded: VersusSim = ron::from_str(&ron::to_string(&sim).unwrap()).unwrap();
+        loaded.occupancy.assert_consistent();
+        assert_eq!(loaded.state_hash(), sim.state_hash());
+        // From there on, a host that rebuilt its own board plays out like the guest
+        let mut rebuilt = sim.rebuilt();
+        for _ in 0..100 {
+            let inputs = [bot_input(&rebuilt, 0), bot_input(&rebuilt, 1)];
+            rebuilt.step(inputs);
+            loaded.step(inputs);
+            assert_eq!(loaded.state_hash(), rebuilt.state_hash());
+        }
+    }
+
+    #[test]
+    fn broken_snapshots_are_rejected() {
+        let state = |edit: fn(&mut SimState)| {
+            let mut state = SimState::from(VersusSim::new(1, GameRules::DEFAULT));
+            edit(&mut state);
+            VersusSim::try_from(state).err()
+        };
+        assert!(state(|_| {}).is_none());
+        assert!(state(|state| state.rivals[0].cells.clear()).is_some());
+        assert!(state(|state| state.rivals[1].cells.push(IVec2::new(-1, 0))).is_some());
+        assert!(state(|state| state.rivals[0].direction = IVec2::ONE).is_some());
+        assert!(state(|state| state.apples.push(state.rivals[0].cells[0])).is_some());
+    }
+
+    #[test]
+    fn long_starts_stack_against_the_wall() {
+        let rules = GameRules {
+            start_length: 40,
+            ..GameRules::DEFAULT
+        };
+        let sim = VersusSim::new(2, rules);
+        for rival in &sim.rivals {
+            assert_eq!(rival.body.len(), 40);
+            assert!(
+                rival
+                    .body
+                    .iter()
+                    .all(|segment| rules.is_inside(segment.cell))
+            );
+        }
+        sim.occupancy.assert_consistent();
+        let sim = played(rules, 30);
+        sim.occupancy.assert_consistent();
+    }
+
+    #[test]
+    fn head_on_is_a_draw() {
+        let rules = GameRules {
+            side: 4,
+            apples: 1,
+            ..GameRules::DEFAULT
+        };
+        let state = SimState {
+            tick: 0,
+            seed: 0,
+            draws: 0,
+            rules,
+            rivals: [
+                RivalState {
+                    cells: vec![IVec2::new(1, 2)],
+                    direction: IVec2::X,
+                    apples: 0,
+                },
+                RivalState {
+                    cells: vec![IVec2::new(3, 2)],
+                    direction: IVec2::NEG_X,
+                    apples: 0,
+                },
+            ],
+            apples: vec![IVec2::new(0, 0)],
+            outcome: None,
+        };
+        let mut sim = VersusSim::try_from(state).unwrap();
+        sim.step([KEEP, KEEP]);
+        assert_eq!(sim.outcome, Some(Outcome::Draw));
+        // Nobody moves once the match is over
+        assert_eq!(sim.rivals[0].body.head().cell, IVec2::new(1, 2));
+        sim.step([KEEP, KEEP]);
+        assert_eq!(sim.tick, 1);
+    }
+
+    #[test]
+    fn desync_is_caught_and_fixed_by_a_snapshot() {
+        let mut link = Link::new(LOSSY_LINK, 5);
+        link.run(10);
+        let guest = link.guest.sim.as_mut().unwrap();
+        let forced = guest.tick;
+        guest.rivals[1].apples += 7;
+        link.run(400);
+
+        let agreement = link.agreement();
+        let last_desync = agreement
+            .iter()
+            .rev()
+            .find(|(_, agree)| !agree)
+            .map(|(tick, _)| *tick)
+            .expect("the forced desync is never seen");
+        assert!(last_desync > forced);
+        let (last_tick, agree) = *agreement.last().unwrap();
+        assert!(agree, "still apart at tick {last_tick}");
+        assert!(last_tick > last_desync);
+        let [host, guest] = [&link.host, &link.guest].map(|peer| peer.sim.as_ref().unwrap());
+        assert_eq!(host.rivals[1].apples, guest.rivals[1].apples);
+    }
+
+    #[test]
+    fn bye_disconnects_right_away() {
+        let mut link = Link::new(LOSSY_LINK, 3);
+        link.run(20);
+        link.host.session.close();
+        for _ in 0..5 {
+            link.clock.advance(TICK);
+            link.guest.tick();
+        }
+        assert!(link.guest.session.is_disconnected());
+    }
+
+    #[test]
+    fn silence_disconnects_after_the_timeout() {
+        let mut link = Link::new(LOSSY_LINK, 3);
+        link.run(20);
+        let mut ticks = 0;
+        while !link.guest.session.is_disconnected() {
+            link.clock.advance(TICK);
+            link.guest.tick();
+            ticks += 1;
+            assert!(ticks < 100, "never noticed the host is gone");
+        }
+        // The last packets of the host can still arrive during the first ticks
+        assert!(TICK * ticks > Duration::from_secs(3));
+    }
+}
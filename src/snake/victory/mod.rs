@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+
+use super::localization::Localization;
+use super::menu::{AppMenuExt, MenuAction, MenuId, MenuItem, MenuScreen, MenuStack};
+use super::score::Score;
+
+pub struct VictoryPlugin;
+
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_menu(MenuId::Victory, victory_menu)
+            .add_systems(
+                OnEnter(super::GameState::Victory),
+                (open_menu, spawn_camera, spawn_confetti),
+            )
+            .add_systems(
+                Update,
+                fall_confetti.run_if(in_state(super::GameState::Victory)),
+            )
+            .add_systems(
+                OnExit(super::GameState::Victory),
+                (despawn_camera, close_menu, despawn_confetti),
+            );
+    }
+}
+
+const CONFETTI_COUNT: u64 = 120;
+const CONFETTI_SIZE: f32 = 10.;
+const CONFETTI_COLORS: [Color; 5] = [
+    Color::srgb(0.95, 0.3, 0.3),
+    Color::srgb(0.95, 0.8, 0.2),
+    Color::srgb(0.3, 0.85, 0.4),
+    Color::srgb(0.3, 0.6, 0.95),
+    Color::srgb(0.8, 0.4, 0.9),
+];
+
+/// A piece falling down the screen, positions are in percent of the window so it doesn't need to
+/// know its size.
+#[derive(Component)]
+struct Confetti {
+    /// Percent of the window height per second
+    speed: f32,
+    /// Degrees per second
+    spin: f32,
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+fn spawn_confetti(mut commands: Commands, rng: Res<super::RngResource>) {
+    for _ in 0..CONFETTI_COUNT {
+        let color = CONFETTI_COLORS[rng.random_in_range(0..CONFETTI_COLORS.len() as u64) as usize];
+        commands.spawn((
+            Confetti {
+                speed: 10. + rng.random_in_range(0..25) as f32,
+                spin: rng.random_in_range(0..360) as f32 - 180.,
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(rng.random_in_range(0..100) as f32),
+                // Starts above the window so the pieces keep coming in for a while
+                top: Val::Percent(-(rng.random_in_range(0..100) as f32)),
+                width: Val::Px(CONFETTI_SIZE),
+                height: Val::Px(CONFETTI_SIZE * 0.5),
+                ..default()
+            },
+            BackgroundColor(color),
+            GlobalZIndex(-1),
+        ));
+    }
+}
+
+fn fall_confetti(time: Res<Time>, confetti: Query<(&Confetti, &mut Node, &mut Transform)>) {
+    for (piece, mut node, mut transform) in confetti {
+        if let Val::Percent(top) = node.top {
+            let top = top + piece.speed * time.delta_secs();
+            // Wraps around, the celebration lasts as long as the screen is open
+            node.top = Val::Percent(if top > 100. { top - 110. } else { top });
+        }
+        transform.rotate_z((piece.spin * time.delta_secs()).to_radians());
+    }
+}
+
+fn despawn_confetti(mut commands: Commands, confetti: Query<Entity, With<Confetti>>) {
+    for piece in confetti {
+        commands.entity(piece).despawn();
+    }
+}
+
+fn victory_menu(world: &World) -> MenuScreen {
+    let localization = world.resource::<Localization>();
+    let score = world.resource::<Score>().0;
+    MenuScreen {
+        title: Some("victory.title"),
+        items: vec![
+            MenuItem::Label(localization.get("victory.message")),
+            MenuItem::Label(localization.format("victory.score", &[&score.to_string()])),
+            MenuItem::Button {
+                label: "gameover.retry",
+                action: MenuAction::Run(super::gameover::retry),
+            },
+            MenuItem::Button {
+                label: "gameover.main_menu",
+                action: MenuAction::Run(|world| {
+                    world
+                        .resource_mut::<NextState<super::GameState>>()
+                        .set(super::GameState::Main)
+                }),
+            },
+        ],
+    }
+}
+
+fn open_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.open(MenuId::Victory);
+}
+
+fn close_menu(mut menu_stack: ResMut<MenuStack>) {
+    menu_stack.clear();
+}
+
+fn despawn_camera(mut commands: Commands, camera: Single<Entity, With<Camera2d>>) {
+    commands.entity(*camera).despawn();
+}